@@ -5,7 +5,7 @@ use convert_case::{Case, Casing};
 use openapiv3::{APIKeyLocation, OpenAPI, ReferenceOr, Schema, SecurityScheme};
 use openapiv3 as oa;
 
-use ::hir::{AuthLocation, AuthParam, AuthStrategy, DocFormat, HirSpec, Language, Location, Operation, Record, Ty, Parameter, Doc};
+use ::hir::{ArrayStyle, AuthLocation, AuthParam, AuthStrategy, DocFormat, ErrorResponse, ErrorStatus, HirSpec, Language, Location, Operation, Record, Ty, Parameter, Doc, Webhook};
 pub use record::*;
 pub use resolution::{schema_ref_to_ty, schema_ref_to_ty_already_resolved, schema_to_ty};
 pub use resolution::*;
@@ -18,9 +18,11 @@ mod record;
 
 /// You might need to call add_operation_models after this
 pub fn extract_spec(spec: &OpenAPI) -> Result<HirSpec> {
+    check_ref_cycles(spec)?;
     let mut result = HirSpec::default();
     extract_api_operations(spec, &mut result)?;
     extract_records(spec, &mut result)?;
+    extract_webhooks(spec, &mut result)?;
     let servers = extract_servers(spec)?;
     let security = extract_security_strategies(spec);
 
@@ -29,10 +31,22 @@ pub fn extract_spec(spec: &OpenAPI) -> Result<HirSpec> {
     result.servers = servers;
     result.security = security;
     result.api_docs_url = api_docs_url;
+    result.title = spec.info.title.clone();
+    result.version = spec.info.version.clone();
     sanitize_spec(&mut result);
     Ok(result)
 }
 
+const MERGE_PATCH: &str = "application/merge-patch+json";
+
+/// Whether the operation only accepts its body as a JSON merge patch.
+fn is_merge_patch(operation: &oa::Operation, spec: &OpenAPI) -> bool {
+    let Some(body) = operation.request_body.as_ref().and_then(|b| b.resolve(spec).ok()) else {
+        return false;
+    };
+    body.content.contains_key(MERGE_PATCH) && !body.content.contains_key("application/json")
+}
+
 pub fn is_optional(name: &str, param: &Schema, parent: &Schema) -> bool {
     param.nullable || !parent.required(name)
 }
@@ -49,6 +63,7 @@ pub fn extract_request_schema<'a>(
     let content = body
         .content
         .get("application/json")
+        .or_else(|| body.content.get(MERGE_PATCH))
         .ok_or_else(|| anyhow!("No json body"))?;
     Ok(content.schema.as_ref().expect(&format!("Expecting a ref for {}", operation.operation_id.as_ref().map(|s| s.as_str()).unwrap_or_default())).resolve(spec))
 }
@@ -58,8 +73,10 @@ pub fn extract_param(param: &ReferenceOr<oa::Parameter>, spec: &OpenAPI) -> Resu
 
     let param = param.resolve(spec)?;
     let data = &param.data;
+    let json_content = data.schema().is_none();
     let param_schema_ref = data
         .schema()
+        .or_else(|| json_content_schema(data))
         .ok_or_else(|| anyhow!("No schema for parameter: {:?}", param))?;
     let ty = schema_ref_to_ty(param_schema_ref, spec);
     let schema = param_schema_ref.resolve(spec);
@@ -70,9 +87,32 @@ pub fn extract_param(param: &ReferenceOr<oa::Parameter>, spec: &OpenAPI) -> Resu
         location: param.into(),
         ty,
         example: schema.example.clone(),
+        json_content,
+        default: schema.schema_data.default.clone(),
+        array_style: array_style(&param),
     })
 }
 
+/// Query arrays with an explicit `explode` are written the way the spec says. Without one,
+/// the client's `QueryArrayStyle` decides.
+fn array_style(param: &oa::Parameter) -> Option<ArrayStyle> {
+    let oa::ParameterKind::Query { style: oa::QueryStyle::Form, .. } = &param.kind else {
+        return None;
+    };
+    match param.data.explode? {
+        true => Some(ArrayStyle::Repeat),
+        false => Some(ArrayStyle::Comma),
+    }
+}
+
+/// The schema of a parameter declared with `content: application/json` instead of `schema`.
+fn json_content_schema(data: &oa::ParameterData) -> Option<&ReferenceOr<Schema>> {
+    let oa::ParameterSchemaOrContent::Content(content) = &data.format else {
+        return None;
+    };
+    content.get("application/json")?.schema.as_ref()
+}
+
 pub fn extract_inputs<'a>(
     operation: &'a oa::Operation,
     item: &'a oa::PathItem,
@@ -84,9 +124,10 @@ pub fn extract_inputs<'a>(
         .map(|param| extract_param(param, spec))
         .collect::<Result<Vec<_>, _>>()?;
 
+    // Path-level parameters apply to every operation on the path, unless the operation redefines them.
     let args = item.parameters.iter().map(|param| extract_param(param, spec)).collect::<Result<Vec<_>, _>>()?;
     for param in args {
-        if !inputs.iter().any(|p| p.name == param.name) {
+        if !inputs.iter().any(|p| p.name == param.name && p.location == param.location) {
             inputs.push(param);
         }
     }
@@ -110,6 +151,9 @@ pub fn extract_inputs<'a>(
             doc: None,
             location: Location::Body,
             example: schema.example.clone(),
+            json_content: false,
+            default: None,
+            array_style: None,
         });
     } else if let Ok(props) = schema.properties_iter(spec) {
         let body_args = props.map(|(name, param)| {
@@ -124,6 +168,9 @@ pub fn extract_inputs<'a>(
                 doc: None,
                 location: Location::Body,
                 example: schema.example.clone(),
+                json_content: false,
+                default: None,
+                array_style: None,
             }
         });
         for param in body_args {
@@ -139,15 +186,36 @@ pub fn extract_inputs<'a>(
             doc: None,
             location: Location::Body,
             example: schema.example.clone(),
+            json_content: false,
+            default: None,
+            array_style: None,
         });
     }
     Ok(inputs)
 }
 
-pub fn extract_response_success<'a>(
+/// PATCH bodies tell "leave unchanged" (absent) apart from "clear" (null). Nullable body fields become
+/// `Option<Option<T>>`: unset is absent, `Some(None)` is null.
+fn distinguish_patch_nulls(parameters: &mut [Parameter], operation: &oa::Operation, spec: &OpenAPI) {
+    let Ok(schema) = extract_request_schema(operation, spec) else { return };
+    let Ok(props) = schema.properties_iter(spec) else { return };
+    for (name, prop) in props {
+        if !prop.resolve(spec).nullable {
+            continue;
+        }
+        let Some(param) = parameters.iter_mut().find(|p| p.name == *name && p.location == Location::Body) else {
+            continue;
+        };
+        if !matches!(param.ty, Ty::Option(_)) {
+            param.ty = Ty::Option(Box::new(param.ty.clone()));
+        }
+    }
+}
+
+fn extract_success_response<'a>(
     operation: &'a oa::Operation,
     spec: &'a OpenAPI,
-) -> Option<&'a ReferenceOr<Schema>> {
+) -> Option<&'a oa::Response> {
     use openapiv3::StatusCode;
 
     let response = operation
@@ -157,19 +225,59 @@ pub fn extract_response_success<'a>(
         .or_else(|| operation.responses.responses.get(&StatusCode::Code(201)))
         .or_else(|| operation.responses.responses.get(&StatusCode::Code(202)))
         .or_else(|| operation.responses.responses.get(&StatusCode::Code(204)))
-        .or_else(|| operation.responses.responses.get(&StatusCode::Code(302)));
-    response?;
-    let response = response
-        .unwrap_or_else(|| panic!("No success response for operation {:?}", operation))
-        .resolve(spec)
-        .unwrap();
-    response
+        .or_else(|| operation.responses.responses.get(&StatusCode::Code(302)))?;
+    Some(response.resolve(spec).unwrap())
+}
+
+pub fn extract_response_success<'a>(
+    operation: &'a oa::Operation,
+    spec: &'a OpenAPI,
+) -> Option<&'a ReferenceOr<Schema>> {
+    extract_success_response(operation, spec)?
         .content
         .get("application/json")
         .and_then(|media| media.schema.as_ref())
 }
 
-pub fn extract_operation_doc(operation: &oa::Operation, format: DocFormat) -> Option<Doc> {
+/// Error responses (4xx, 5xx and `default`) whose json body is a component schema.
+pub fn extract_error_responses(operation: &oa::Operation, spec: &OpenAPI) -> Vec<ErrorResponse> {
+    use openapiv3::StatusCode;
+
+    let documented = operation.responses.responses.iter()
+        .filter_map(|(status, response)| {
+            let status = match status {
+                StatusCode::Code(code) if *code >= 400 => ErrorStatus::Code(*code),
+                StatusCode::Range(range) if *range >= 4 => ErrorStatus::Range(*range),
+                _ => return None,
+            };
+            Some((status, response))
+        })
+        .chain(operation.responses.default.iter().map(|response| (ErrorStatus::Default, response)));
+    let mut errors: Vec<ErrorResponse> = documented
+        .filter_map(|(status, response)| {
+            let schema = response.resolve(spec).ok()?
+                .content
+                .get("application/json")?
+                .schema
+                .as_ref()?;
+            if schema.as_ref_str().is_none() {
+                return None;
+            }
+            Some(ErrorResponse { status, ty: schema_ref_to_ty(schema, spec) })
+        })
+        .collect();
+    errors.sort_by_key(|e| e.status);
+    errors
+}
+
+/// The media types of the success response, in the order the spec lists them.
+pub fn extract_response_media_types(operation: &oa::Operation, spec: &OpenAPI) -> Vec<String> {
+    extract_success_response(operation, spec)
+        .map(|response| response.content.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub fn extract_operation_doc(operation: &oa::Operation, spec: &OpenAPI, format: DocFormat) -> Option<Doc> {
     let mut doc_pieces = vec![];
     if let Some(summary) = operation.summary.as_ref() {
         if !summary.is_empty() {
@@ -183,9 +291,17 @@ pub fn extract_operation_doc(operation: &oa::Operation, format: DocFormat) -> Op
             }
         }
     }
+    for param in operation.parameters.iter().filter_map(|p| p.as_item()) {
+        doc_pieces.extend(named_example_docs(&param.data.examples, spec));
+    }
+    if let Some(content) = operation.request_body.as_ref()
+        .and_then(|body| body.resolve(spec).ok())
+        .and_then(|body| body.content.get("application/json")) {
+        doc_pieces.extend(named_example_docs(&content.examples, spec));
+    }
     if let Some(external_docs) = operation.external_docs.as_ref() {
         doc_pieces.push(match format {
-            DocFormat::Markdown => format!("See endpoint docs at <{}>.", external_docs.url),
+            DocFormat::Markdown => format!("See endpoint docs at {}.", external_docs_link(external_docs)),
             DocFormat::Rst => format!(
                 "See endpoint docs at `{url} <{url}>`_.",
                 url = external_docs.url
@@ -200,10 +316,57 @@ pub fn extract_operation_doc(operation: &oa::Operation, format: DocFormat) -> Op
 }
 
 pub fn extract_schema_docs(schema: &Schema) -> Option<Doc> {
-    schema
-        .description
-        .as_ref()
-        .map(|d| Doc(d.trim().to_string()))
+    let mut doc_pieces: Vec<String> = schema.description.iter()
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .collect();
+    // 3.1 schemas list their examples in an array.
+    if let Some(examples) = schema.data.extensions.get("examples").and_then(|v| v.as_array()) {
+        doc_pieces.extend(examples.iter().map(|value| example_doc(None, value)));
+    }
+    if let Some(external_docs) = schema.external_docs.as_ref() {
+        doc_pieces.push(format!("See {}.", external_docs_link(external_docs)));
+    }
+    if doc_pieces.is_empty() {
+        None
+    } else {
+        Some(Doc(doc_pieces.join("\n\n")))
+    }
+}
+
+/// A markdown link to `externalDocs`, titled with its description if it has one.
+fn external_docs_link(docs: &oa::ExternalDocumentation) -> String {
+    match docs.description.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        Some(description) => format!("[{}]({})", description, docs.url),
+        None => format!("<{}>", docs.url),
+    }
+}
+
+/// Examples longer than this many bytes are cut off in doc comments.
+const MAX_DOC_EXAMPLE_LEN: usize = 1000;
+
+/// An example value as a fenced json block.
+fn example_doc(name: Option<&str>, value: &serde_json::Value) -> String {
+    let mut json = serde_json::to_string_pretty(value).unwrap_or_default();
+    if json.len() > MAX_DOC_EXAMPLE_LEN {
+        let end = (0..=MAX_DOC_EXAMPLE_LEN).rev().find(|&i| json.is_char_boundary(i)).unwrap_or(0);
+        json.truncate(end);
+        json.push_str("\n...");
+    }
+    match name {
+        Some(name) => format!("Example `{}`:\n```json\n{}\n```", name, json),
+        None => format!("Example:\n```json\n{}\n```", json),
+    }
+}
+
+/// Examples may be `$ref`s to `components/examples`.
+fn named_example_docs(examples: &indexmap::IndexMap<String, ReferenceOr<oa::Example>>, spec: &OpenAPI) -> Vec<String> {
+    examples.iter()
+        .filter_map(|(name, example)| {
+            let value = example.resolve(spec).ok()?.value.as_ref()?;
+            Some(example_doc(Some(name), value))
+        })
+        .collect()
 }
 
 pub fn make_name_from_method_and_url(method: &str, url: &str) -> String {
@@ -236,17 +399,38 @@ pub fn extract_api_operations(spec: &OpenAPI, result: &mut HirSpec) -> Result<()
                 .replace(".", "_"),
             None => make_name_from_method_and_url(method, path),
         };
-        let doc = extract_operation_doc(operation, DocFormat::Markdown);
+        let scopes = extract_required_scopes(operation);
+        let doc = extract_operation_doc(operation, spec, DocFormat::Markdown);
+        let doc = scopes_doc(doc, &scopes);
         let mut parameters = extract_inputs(operation, item, spec)?;
+        let merge_patch = is_merge_patch(operation, spec);
+        if method == "patch" || merge_patch {
+            distinguish_patch_nulls(&mut parameters, operation, spec);
+        }
         parameters.sort_by(|a, b| a.name.cmp(&b.name));
-        let response_success = extract_response_success(operation, spec);
+        // HEAD and OPTIONS responses are never parsed, so any schema the spec gives them is ignored.
+        let headers_only = matches!(method, "head" | "options");
+        let response_success = extract_response_success(operation, spec).filter(|_| !headers_only);
+        let response_media_types = if headers_only {
+            Vec::new()
+        } else {
+            extract_response_media_types(operation, spec)
+        };
+        let errors = extract_error_responses(operation, spec);
         let mut needs_response_model = None;
         let ret = match response_success {
             None => Ty::Unit,
             Some(ReferenceOr::Item(s)) => {
                 if matches!(s.kind, oa::SchemaKind::Type(oa::Type::Object(_))) {
-                    needs_response_model = Some(s);
-                    Ty::model(&format!("{}Response", name))
+                    let response_name = inline_schema_name(s, format!("{}Response", name), spec, result);
+                    let ty = Ty::model(&response_name);
+                    needs_response_model = Some((response_name, s));
+                    ty
+                } else if let Some(item) = inline_object_items(s) {
+                    let item_name = inline_schema_name(item, format!("{}ResponseItem", name), spec, result);
+                    let ty = Ty::Array(Box::new(Ty::model(&item_name)));
+                    needs_response_model = Some((item_name, item));
+                    ty
                 } else {
                     schema_to_ty(s, spec)
                 }
@@ -256,8 +440,10 @@ pub fn extract_api_operations(spec: &OpenAPI, result: &mut HirSpec) -> Result<()
             }
         };
 
-        if let Some(s) = needs_response_model {
-            let response_name = format!("{}Response", name);
+        if let Some((response_name, s)) = needs_response_model {
+            for rec in create_nested_records(&response_name, s, spec) {
+                result.schemas.insert(rec.name().to_string(), rec);
+            }
             result.schemas.insert(response_name.clone(), create_record(&response_name, s, spec));
         }
         result.operations.push(Operation {
@@ -267,11 +453,99 @@ pub fn extract_api_operations(spec: &OpenAPI, result: &mut HirSpec) -> Result<()
             ret,
             path: path.to_string(),
             method: method.to_string(),
+            tags: operation.tags.clone(),
+            response_media_types,
+            server: operation.servers.first()
+                .or_else(|| item.servers.first())
+                .map(|s| s.url.clone()),
+            errors,
+            envelope: None,
+            scopes,
+            merge_patch,
         });
     }
     Ok(())
 }
 
+fn extract_required_scopes(operation: &oa::Operation) -> Vec<String> {
+    let Some(scopes) = operation.extensions.get("x-required-scopes").and_then(|v| v.as_array()) else {
+        return Vec::new();
+    };
+    scopes.iter().filter_map(|s| s.as_str()).map(|s| s.to_string()).collect()
+}
+
+fn scopes_doc(doc: Option<Doc>, scopes: &[String]) -> Option<Doc> {
+    if scopes.is_empty() {
+        return doc;
+    }
+    let scopes = scopes.iter().map(|s| format!("`{}`", s)).collect::<Vec<_>>().join(", ");
+    let line = format!("Required scopes: {}", scopes);
+    Some(Doc(match doc {
+        Some(Doc(doc)) => format!("{}\n\n{}", doc, line),
+        None => line,
+    }))
+}
+
+/// The items of a bare array, when they're an inline object that needs its own model.
+fn inline_object_items(schema: &Schema) -> Option<&Schema> {
+    let oa::SchemaKind::Type(oa::Type::Array(oa::ArrayType { items: Some(items), .. })) = &schema.kind else {
+        return None;
+    };
+    let item = items.as_item()?;
+    matches!(item.kind, oa::SchemaKind::Type(oa::Type::Object(_))).then_some(item)
+}
+
+/// Inline schemas are named after their `title` when they have one, since it's usually nicer than
+/// the synthesized name. Titles that would collide with another model fall back to the synthesized name.
+fn inline_schema_name(schema: &Schema, synthesized: String, spec: &OpenAPI, result: &HirSpec) -> String {
+    let Some(title) = schema.title.as_ref().filter(|t| !t.trim().is_empty()) else {
+        return synthesized;
+    };
+    let type_name = title.to_case(Case::Pascal);
+    let taken = spec.schemas.keys()
+        .chain(result.schemas.keys())
+        .any(|name| name.to_case(Case::Pascal) == type_name);
+    if taken {
+        synthesized
+    } else {
+        title.clone()
+    }
+}
+
+/// Webhooks are inbound requests, so the payload is the request body of the webhook's POST operation.
+pub fn extract_webhooks(spec: &OpenAPI, result: &mut HirSpec) -> Result<()> {
+    for (name, item) in &spec.webhooks {
+        let Some(item) = item.as_item() else {
+            warn!("Skipping webhook {}: references to webhooks are not supported", name);
+            continue;
+        };
+        let Some(operation) = item.post.as_ref() else {
+            warn!("Skipping webhook {}: only POST webhooks are supported", name);
+            continue;
+        };
+        let Some(schema_ref) = operation.request_body.as_ref()
+            .and_then(|body| body.resolve(spec).ok())
+            .and_then(|body| body.content.get("application/json"))
+            .and_then(|content| content.schema.as_ref()) else {
+            warn!("Skipping webhook {}: no json payload", name);
+            continue;
+        };
+        let ty = match schema_ref {
+            ReferenceOr::Reference { .. } => schema_ref_to_ty(schema_ref, spec),
+            ReferenceOr::Item(schema) => {
+                let payload_name = inline_schema_name(schema, format!("{}Webhook", name.to_case(Case::Pascal)), spec, result);
+                result.schemas.insert(payload_name.clone(), create_record(&payload_name, schema, spec));
+                Ty::model(&payload_name)
+            }
+        };
+        result.webhooks.push(Webhook {
+            name: name.clone(),
+            doc: extract_operation_doc(operation, spec, DocFormat::Markdown),
+            ty,
+        });
+    }
+    Ok(())
+}
 
 fn extract_servers(spec: &OpenAPI) -> Result<BTreeMap<String, String>> {
     let mut servers = BTreeMap::new();
@@ -305,23 +579,11 @@ fn extract_api_docs_link(spec: &OpenAPI) -> Option<String> {
 /// Remove from the HirSpec anything that appears to be unused
 fn remove_unused(spec: &mut HirSpec) {
     let mut used: HashSet<String> = HashSet::new();
-    for (_name, schema) in spec.schemas.iter() {
-        for field in schema.fields() {
-            if let Some(name) = &field.ty.inner_model() {
-                used.insert(name.to_string());
-            };
-        }
-    }
-    for operation in spec.operations.iter() {
-        if let Some(name) = &operation.ret.inner_model() {
+    spec.visit_tys(|ty| {
+        if let Ty::Model(name) = ty {
             used.insert(name.to_string());
-        };
-        for param in operation.parameters.iter() {
-            if let Some(name) = &param.ty.inner_model() {
-                used.insert(name.to_string());
-            };
         }
-    }
+    });
     let count_before = spec.schemas.len();
     spec.schemas.retain(|name, _| {
         let needed = used.contains(name) || name.ends_with("Webhook");
@@ -486,4 +748,736 @@ mod tests {
         let op_name = make_name_from_method_and_url(method, url);
         assert_eq!(op_name, "get_user_account_by_id");
     }
+
+    #[test]
+    fn test_path_level_parameters() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    parameters:
+      - name: limit
+        in: query
+        schema:
+          type: integer
+      - name: limit
+        in: header
+        schema:
+          type: integer
+    get:
+      operationId: listPets
+      responses:
+        '204':
+          description: No content
+    post:
+      operationId: createPet
+      parameters:
+        - name: limit
+          in: query
+          required: true
+          schema:
+            type: integer
+      responses:
+        '204':
+          description: No content
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+
+        let get = result.operations.iter().find(|o| o.name == "listPets").unwrap();
+        let query = get.parameters.iter().find(|p| p.location == Location::Query).unwrap();
+        assert_eq!(query.name, "limit");
+        assert!(query.optional);
+        assert!(get.parameters.iter().any(|p| p.name == "limit" && p.location == Location::Header));
+
+        let post = result.operations.iter().find(|o| o.name == "createPet").unwrap();
+        let query = post.parameters.iter().filter(|p| p.location == Location::Query).collect::<Vec<_>>();
+        assert_eq!(query.len(), 1);
+        assert!(!query[0].optional);
+        assert!(post.parameters.iter().any(|p| p.name == "limit" && p.location == Location::Header));
+    }
+
+    #[test]
+    fn test_response_media_types() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Reports
+  version: 1.0.0
+paths:
+  /report:
+    get:
+      operationId: getReport
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                type: string
+            text/csv:
+              schema:
+                type: string
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let operation = result.get_operation("getReport").unwrap();
+        assert_eq!(operation.response_media_types, vec!["application/json", "text/csv"]);
+    }
+
+    #[test]
+    fn test_named_examples_in_docs() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    post:
+      operationId: createPet
+      summary: Create a pet
+      requestBody:
+        content:
+          application/json:
+            schema:
+              type: object
+              properties:
+                name:
+                  type: string
+            examples:
+              dog:
+                value:
+                  name: Rex
+      responses:
+        '204':
+          description: No content
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let Doc(doc) = result.get_operation("createPet").unwrap().doc.clone().unwrap();
+        assert!(doc.starts_with("Create a pet\n\n"));
+        assert!(doc.contains("Example `dog`:\n```json\n{\n  \"name\": \"Rex\"\n}\n```"));
+
+        let long = serde_json::Value::String("x".repeat(2 * MAX_DOC_EXAMPLE_LEN));
+        let doc = example_doc(None, &long);
+        assert!(doc.len() < MAX_DOC_EXAMPLE_LEN + 50);
+        assert!(doc.ends_with("\n...\n```"));
+    }
+
+    #[test]
+    fn test_component_example_in_docs() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    post:
+      operationId: createPet
+      requestBody:
+        content:
+          application/json:
+            schema:
+              type: object
+              properties:
+                name:
+                  type: string
+            examples:
+              cat:
+                $ref: '#/components/examples/Cat'
+      responses:
+        '204':
+          description: No content
+components:
+  examples:
+    Cat:
+      value:
+        name: Tom
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let Doc(doc) = result.get_operation("createPet").unwrap().doc.clone().unwrap();
+        assert!(doc.contains("Example `cat`:\n```json\n{\n  \"name\": \"Tom\"\n}\n```"));
+    }
+
+    #[test]
+    fn test_external_docs() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      externalDocs:
+        description: Listing pets
+        url: https://docs.example.com/pets/list
+      responses:
+        '204':
+          description: No content
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let Doc(doc) = result.get_operation("listPets").unwrap().doc.clone().unwrap();
+        assert_eq!(doc, "See endpoint docs at [Listing pets](https://docs.example.com/pets/list).");
+
+        let schema: Schema = serde_yaml::from_str(r#"
+type: object
+description: A pet.
+externalDocs:
+  url: https://docs.example.com/pets
+"#).unwrap();
+        let Doc(doc) = extract_schema_docs(&schema).unwrap();
+        assert_eq!(doc, "A pet.\n\nSee <https://docs.example.com/pets>.");
+    }
+
+    #[test]
+    fn test_required_scopes() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    delete:
+      operationId: deletePets
+      summary: Delete every pet.
+      x-required-scopes: [pets:read, pets:write]
+      responses:
+        '204':
+          description: No content
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let operation = result.get_operation("deletePets").unwrap();
+        assert_eq!(operation.scopes, vec!["pets:read", "pets:write"]);
+        assert_eq!(operation.doc.as_ref().unwrap().0, "Delete every pet.\n\nRequired scopes: `pets:read`, `pets:write`");
+    }
+
+    #[test]
+    fn test_default_error_response() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets/{id}:
+    get:
+      operationId: getPet
+      parameters:
+        - name: id
+          in: path
+          required: true
+          schema:
+            type: string
+      responses:
+        '204':
+          description: No content
+        default:
+          description: Anything else
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Problem'
+        '404':
+          description: Not found
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/NotFound'
+components:
+  schemas:
+    Problem:
+      type: object
+      properties:
+        detail:
+          type: string
+    NotFound:
+      type: object
+      properties:
+        id:
+          type: string
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let errors = &result.get_operation("getPet").unwrap().errors;
+        // `default` sorts last, so it only catches the statuses that aren't listed.
+        let statuses = errors.iter().map(|e| e.status).collect::<Vec<_>>();
+        assert_eq!(statuses, vec![ErrorStatus::Code(404), ErrorStatus::Default]);
+        assert!(matches!(&errors[1].ty, Ty::Model(name) if name == "Problem"));
+    }
+
+    #[test]
+    fn test_explode_array_style() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      parameters:
+        - name: ids
+          in: query
+          explode: false
+          schema:
+            type: array
+            items:
+              type: integer
+        - name: tags
+          in: query
+          schema:
+            type: array
+            items:
+              type: string
+      responses:
+        '204':
+          description: No content
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let params = &result.get_operation("listPets").unwrap().parameters;
+        assert_eq!(params[0].array_style, Some(ArrayStyle::Comma));
+        assert_eq!(params[1].array_style, None);
+    }
+
+    #[test]
+    fn test_head_operation() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    head:
+      operationId: petsExist
+      responses:
+        '200':
+          description: Headers only
+          headers:
+            X-Total-Count:
+              schema:
+                type: integer
+          content:
+            application/json:
+              schema:
+                type: object
+                properties:
+                  count:
+                    type: integer
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let operation = result.get_operation("petsExist").unwrap();
+        assert!(operation.headers_only());
+        assert!(matches!(operation.ret, Ty::Unit));
+        assert!(operation.response_media_types.is_empty());
+        assert!(result.schemas.is_empty());
+    }
+
+    #[test]
+    fn test_patch_nullable_body() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    put:
+      operationId: replacePet
+      requestBody:
+        content:
+          application/json:
+            schema:
+              $ref: '#/components/schemas/PetUpdate'
+      responses:
+        '204':
+          description: Replaced
+    patch:
+      operationId: updatePet
+      requestBody:
+        content:
+          application/json:
+            schema:
+              $ref: '#/components/schemas/PetUpdate'
+      responses:
+        '204':
+          description: Updated
+components:
+  schemas:
+    PetUpdate:
+      type: object
+      properties:
+        name:
+          type: string
+        nickname:
+          type: string
+          nullable: true
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let param = |op: &str, name: &str| {
+            let operation = result.get_operation(op).unwrap();
+            operation.parameters.iter().find(|p| p.name == name).unwrap().clone()
+        };
+        let nickname = param("updatePet", "nickname");
+        assert!(nickname.optional);
+        let Ty::Option(inner) = nickname.ty else { panic!("expected a double option") };
+        assert!(matches!(*inner, Ty::String));
+        // Non-nullable fields can only be absent or present.
+        assert!(matches!(param("updatePet", "name").ty, Ty::String));
+        // Outside PATCH, null still means absent.
+        assert!(matches!(param("replacePet", "nickname").ty, Ty::String));
+        assert!(!result.get_operation("updatePet").unwrap().merge_patch);
+    }
+
+    #[test]
+    fn test_merge_patch_body() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    patch:
+      operationId: updatePet
+      requestBody:
+        content:
+          application/merge-patch+json:
+            schema:
+              type: object
+              properties:
+                nickname:
+                  type: string
+                  nullable: true
+      responses:
+        '204':
+          description: Updated
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let operation = result.get_operation("updatePet").unwrap();
+        assert!(operation.merge_patch);
+        let nickname = operation.parameters.iter().find(|p| p.name == "nickname").unwrap();
+        assert!(matches!(nickname.ty, Ty::Option(_)));
+    }
+
+    #[test]
+    fn test_param_default() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      parameters:
+        - name: limit
+          in: query
+          schema:
+            type: integer
+            default: 20
+      responses:
+        '204':
+          description: No content
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let param = &result.get_operation("listPets").unwrap().parameters[0];
+        assert!(param.optional);
+        assert_eq!(param.default, Some(serde_json::json!(20)));
+    }
+
+    #[test]
+    fn test_json_content_query_param() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    get:
+      operationId: searchPets
+      parameters:
+        - name: filter
+          in: query
+          required: true
+          content:
+            application/json:
+              schema:
+                type: object
+                properties:
+                  color:
+                    type: string
+      responses:
+        '204':
+          description: No content
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let param = &result.get_operation("searchPets").unwrap().parameters[0];
+        assert_eq!(param.name, "filter");
+        assert!(param.json_content);
+        assert_eq!(param.location, Location::Query);
+    }
+
+    #[test]
+    fn test_top_level_array_response() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                type: array
+                items:
+                  $ref: '#/components/schemas/Pet'
+  /owners:
+    get:
+      operationId: listOwners
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                type: array
+                items:
+                  type: object
+                  properties:
+                    name:
+                      type: string
+components:
+  schemas:
+    Pet:
+      type: object
+      properties:
+        name:
+          type: string
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let Ty::Array(inner) = &result.get_operation("listPets").unwrap().ret else { panic!() };
+        assert!(matches!(inner.as_ref(), Ty::Model(name) if name == "Pet"));
+        let Ty::Array(inner) = &result.get_operation("listOwners").unwrap().ret else { panic!() };
+        assert!(matches!(inner.as_ref(), Ty::Model(name) if name == "listOwnersResponseItem"));
+        assert!(result.schemas.contains_key("listOwnersResponseItem"));
+    }
+
+    #[test]
+    fn test_inline_schema_title() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /status:
+    get:
+      operationId: getStatus
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                title: Service Status
+                type: object
+                properties:
+                  ok:
+                    type: boolean
+  /pets:
+    get:
+      operationId: listPets
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                title: pet
+                type: object
+                properties:
+                  name:
+                    type: string
+components:
+  schemas:
+    Pet:
+      type: object
+      properties:
+        name:
+          type: string
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let status = result.get_operation("getStatus").unwrap();
+        assert!(matches!(&status.ret, Ty::Model(name) if name == "Service Status"));
+        assert!(result.schemas.contains_key("Service Status"));
+        // `pet` would collide with the `Pet` component.
+        let pets = result.get_operation("listPets").unwrap();
+        assert!(matches!(&pets.ret, Ty::Model(name) if name == "listPetsResponse"));
+    }
+
+    #[test]
+    fn test_path_level_servers() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+servers:
+  - url: https://api.example.com
+paths:
+  /uploads:
+    servers:
+      - url: https://uploads.example.com
+    get:
+      operationId: listUploads
+      responses:
+        '204':
+          description: OK
+    post:
+      operationId: createUpload
+      servers:
+        - url: https://bulk.example.com
+      responses:
+        '204':
+          description: OK
+  /pets:
+    get:
+      operationId: listPets
+      responses:
+        '204':
+          description: OK
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        let list = result.get_operation("listUploads").unwrap();
+        assert_eq!(list.server.as_deref(), Some("https://uploads.example.com"));
+        let create = result.get_operation("createUpload").unwrap();
+        assert_eq!(create.server.as_deref(), Some("https://bulk.example.com"));
+        // Root-level servers become the client's base url instead.
+        assert!(result.get_operation("listPets").unwrap().server.is_none());
+    }
+
+    #[test]
+    fn test_primitive_response() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /token:
+    get:
+      operationId: getToken
+      responses:
+        '200':
+          description: A bare JSON string
+          content:
+            application/json:
+              schema:
+                type: string
+  /pets/count:
+    get:
+      operationId: countPets
+      responses:
+        '200':
+          description: A bare JSON number
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Count'
+components:
+  schemas:
+    Count:
+      type: integer
+"#).unwrap();
+        let mut result = HirSpec::default();
+        extract_api_operations(&spec, &mut result).unwrap();
+        assert!(matches!(result.get_operation("getToken").unwrap().ret, Ty::String));
+        // A reference to a primitive component is inlined, rather than becoming a model.
+        assert!(matches!(result.get_operation("countPets").unwrap().ret, Ty::Integer { .. }));
+        assert!(result.schemas.is_empty());
+    }
+
+    #[test]
+    fn test_tuple_of_models() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pairs:
+    get:
+      operationId: getPair
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Pairing'
+components:
+  schemas:
+    Pairing:
+      type: object
+      required: [pair]
+      properties:
+        pair:
+          type: array
+          prefixItems:
+            - $ref: '#/components/schemas/Owner'
+            - $ref: '#/components/schemas/Pet'
+    Owner:
+      type: object
+      properties:
+        name:
+          type: string
+    Pet:
+      type: object
+      properties:
+        name:
+          type: string
+"#).unwrap();
+        let mut result = extract_spec(&spec).unwrap();
+        // `Pet` is only referenced as the second element of the tuple.
+        assert!(result.schemas.contains_key("Owner"));
+        assert!(result.schemas.contains_key("Pet"));
+
+        result.rename_models(|name| format!("Acme {}", name));
+        let pairing = result.get_record("Acme Pairing").unwrap();
+        let pair = pairing.fields().next().unwrap();
+        let Ty::Tuple(items) = &pair.ty else { panic!("expected a tuple, got {:?}", pair.ty) };
+        assert!(matches!(&items[0], Ty::Model(name) if name == "Acme Owner"));
+        assert!(matches!(&items[1], Ty::Model(name) if name == "Acme Pet"));
+        assert!(result.schemas.contains_key("Acme Pet"));
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use anyhow::Result;
 use indexmap::IndexMap;
@@ -7,7 +7,7 @@ use indexmap::IndexMap;
 use openapiv3::{ObjectType, OpenAPI, ReferenceOr, Schema, SchemaData, SchemaKind, SchemaReference, StringType, Type, RefOrMap};
 use tracing::warn;
 
-use hir::{Doc, HirField, Record, StrEnum, Struct, NewType, HirSpec};
+use hir::{Doc, HirField, Record, StrEnum, Struct, NewType, HirSpec, Ty, Union, UnionVariant};
 
 use crate::extractor;
 use crate::child_schemas::ChildSchemas;
@@ -30,11 +30,70 @@ fn properties_to_fields(properties: &RefOrMap<Schema>, schema: &Schema, spec: &O
                 doc: extractor::extract_schema_docs(field_schema),
                 example: None,
                 flatten: false,
+                comma_separated: is_comma_separated(field_schema),
+                aliases: extract_aliases(field_schema),
+                read_only: field_schema.read_only,
+                write_only: field_schema.write_only,
+                internal: is_internal(field_schema),
             })
         })
         .collect()
 }
 
+/// Arrays marked with `x-comma-separated: true` are (de)serialized as a comma-joined string.
+fn is_comma_separated(schema: &Schema) -> bool {
+    matches!(schema.kind, SchemaKind::Type(Type::Array(_))) && schema.data.extensions.get("x-comma-separated")
+        .and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+/// `x-aliases` lists former names of a property that the API still accepts.
+fn extract_aliases(schema: &Schema) -> Vec<String> {
+    schema.data.extensions.get("x-aliases")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// `dependentRequired` is a 3.1 keyword. `x-dependent-required` lets 3.0 specs express the same rule.
+fn extract_dependent_required(schema_data: &SchemaData) -> BTreeMap<String, Vec<String>> {
+    let Some(rules) = schema_data.extensions.get("dependentRequired")
+        .or_else(|| schema_data.extensions.get("x-dependent-required"))
+        .and_then(|v| v.as_object()) else {
+        return BTreeMap::new();
+    };
+    let mut rules: BTreeMap<String, Vec<String>> = rules.iter().map(|(name, required)| {
+        (name.clone(), string_array(required))
+    }).collect();
+    if let Some((trigger, required)) = extract_if_then(schema_data) {
+        rules.entry(trigger).or_default().extend(required);
+    }
+    rules
+}
+
+fn string_array(value: &serde_json::Value) -> Vec<String> {
+    value.as_array()
+        .map(|a| a.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// `if: {required: [a]}, then: {required: [b, ...]}` is the same rule as `dependentRequired: {a: [b, ...]}`.
+/// Any other conditional can't be checked, so it's ignored.
+fn extract_if_then(schema_data: &SchemaData) -> Option<(String, Vec<String>)> {
+    let condition = schema_data.extensions.get("if")?;
+    let simple = || {
+        let condition = condition.as_object().filter(|c| c.len() == 1)?;
+        let [trigger]: [String; 1] = string_array(condition.get("required")?).try_into().ok()?;
+        let then = schema_data.extensions.get("then")?.as_object().filter(|t| t.len() == 1)?;
+        let required = string_array(then.get("required")?);
+        (!schema_data.extensions.contains_key("else")).then_some((trigger, required))
+    };
+    let rule = simple();
+    if rule.is_none() {
+        warn!("Only `if`/`then` rules on the presence of a single property are supported. Ignoring the condition.");
+    }
+    rule
+}
+
 pub fn effective_length(all_of: &[ReferenceOr<Schema>]) -> usize {
     let mut length = 0;
     for schema_ref in all_of {
@@ -49,6 +108,9 @@ pub fn effective_length(all_of: &[ReferenceOr<Schema>]) -> usize {
 
 pub fn create_record(name: &str, schema: &Schema, spec: &OpenAPI) -> Record {
     let name = name.to_string();
+    if let Some(union) = create_discriminated_union(&name, schema, spec) {
+        return Record::Union(union);
+    }
     match &schema.kind {
         // The base case, a regular object
         SchemaKind::Type(Type::Object(ObjectType { properties, .. })) => {
@@ -57,7 +119,8 @@ pub fn create_record(name: &str, schema: &Schema, spec: &OpenAPI) -> Record {
                 name,
                 fields,
                 nullable: schema.nullable,
-                docs: schema.description.as_ref().map(|d| Doc(d.trim().to_string())),
+                docs: extractor::extract_schema_docs(schema),
+                dependent_required: extract_dependent_required(schema),
             })
         }
         // An enum
@@ -73,6 +136,7 @@ pub fn create_record(name: &str, schema: &Schema, spec: &OpenAPI) -> Record {
                     docs: schema.description.as_ref().map(|d| Doc(d.clone())),
                 })
             }
+        SchemaKind::OneOf { one_of } => Record::Union(create_union(name, one_of, schema, spec)),
         // A newtype with multiple fields
         SchemaKind::AllOf { all_of } => {
             let all_of = all_of.as_slice();
@@ -95,12 +159,36 @@ pub fn create_record(name: &str, schema: &Schema, spec: &OpenAPI) -> Record {
                 doc: None,
                 example: None,
                 flatten: false,
+                comma_separated: false,
+                aliases: Vec::new(),
+                read_only: false,
+                write_only: false,
+                internal: false,
             }],
             docs: schema.description.as_ref().map(|d| Doc(d.clone())),
+            not: excluded_json_type(schema, spec),
         }),
     }
 }
 
+/// The JSON type excluded by a `not: {type: ...}` schema. Other `not` schemas can't be checked.
+fn excluded_json_type(schema: &Schema, spec: &OpenAPI) -> Option<String> {
+    let SchemaKind::Not { not } = &schema.kind else {
+        return None;
+    };
+    let ty = match &not.resolve(spec).kind {
+        SchemaKind::Type(Type::String(_)) => "string",
+        SchemaKind::Type(Type::Number(_)) => "number",
+        SchemaKind::Type(Type::Integer(_)) => "integer",
+        SchemaKind::Type(Type::Boolean {}) => "boolean",
+        SchemaKind::Type(Type::Array(_)) => "array",
+        SchemaKind::Type(Type::Object(_)) => "object",
+        SchemaKind::Any(any) if any.typ.as_deref() == Some("null") => "null",
+        _ => return None,
+    };
+    Some(ty.to_string())
+}
+
 
 fn create_field(field_schema_ref: &ReferenceOr<Schema>, spec: &OpenAPI) -> HirField {
     let field_schema = field_schema_ref.resolve(spec);
@@ -112,13 +200,197 @@ fn create_field(field_schema_ref: &ReferenceOr<Schema>, spec: &OpenAPI) -> HirFi
     let optional = field_schema.nullable;
     let example = field_schema.example.clone();
     let doc = field_schema.description.clone().map(Doc);
-    HirField { ty, optional, doc, example, flatten: false }
+    let comma_separated = is_comma_separated(field_schema);
+    let aliases = extract_aliases(field_schema);
+    let read_only = field_schema.read_only;
+    let write_only = field_schema.write_only;
+    let internal = is_internal(field_schema);
+    HirField { ty, optional, doc, example, flatten: false, comma_separated, aliases, read_only, write_only, internal }
+}
+
+fn create_union(name: String, one_of: &[ReferenceOr<Schema>], schema: &Schema, spec: &OpenAPI) -> Union {
+    let mut variants: Vec<UnionVariant> = Vec::new();
+    for (i, variant) in one_of.iter().enumerate() {
+        let field = create_field(variant, spec);
+        let mut variant_name = match variant {
+            ReferenceOr::Reference { reference } => extractor::get_name(SchemaReference::from_str(reference)),
+            ReferenceOr::Item(item) => item.title.clone().unwrap_or_else(|| variant_name_for_ty(&field.ty, i)),
+        };
+        if variants.iter().any(|v| v.name == variant_name) {
+            variant_name = format!("{}{}", variant_name, i);
+        }
+        let fields = match variant {
+            ReferenceOr::Item(item @ Schema { kind: SchemaKind::Type(Type::Object(ObjectType { properties, .. })), .. }) => {
+                properties_to_fields(properties, item, spec)
+            }
+            _ => BTreeMap::new(),
+        };
+        variants.push(UnionVariant { name: variant_name, field, fields, tag_value: None });
+    }
+    Union {
+        name,
+        variants,
+        docs: schema.description.as_ref().map(|d| Doc(d.clone())),
+        tag: extension_str(schema, "x-serde-tag"),
+        content: extension_str(schema, "x-serde-content"),
+    }
+}
+
+/// A base schema with a `discriminator` but no `oneOf` becomes a tagged union over its subtypes,
+/// which are the component schemas that reference it from an `allOf`.
+///
+/// The subtypes keep the tag, so they serialize completely on their own. serde writes the tag of a
+/// tagged union itself, so the variants are struct variants with the subtypes' other fields.
+fn create_discriminated_union(name: &str, schema: &Schema, spec: &OpenAPI) -> Option<Union> {
+    let discriminator = schema.data.discriminator.as_ref()?;
+    if !matches!(schema.kind, SchemaKind::Type(Type::Object(_))) {
+        return None;
+    }
+    let base_ref = format!("#/components/schemas/{}", name);
+    let variants: Vec<UnionVariant> = spec.schemas.iter()
+        .filter_map(|(subtype, s)| match s.as_item() {
+            Some(Schema { kind: SchemaKind::AllOf { all_of }, .. }) if all_of.iter().any(|m| m.as_ref_str() == Some(base_ref.as_str())) => {
+                Some((subtype, all_of))
+            }
+            _ => None,
+        })
+        .map(|(subtype, all_of)| {
+            // Without a mapping, the tag value is the schema name.
+            let tag_value = discriminator.mapping.iter()
+                .find(|(_, target)| target.rsplit('/').next() == Some(subtype.as_str()))
+                .map(|(value, _)| value.clone())
+                .unwrap_or_else(|| subtype.clone());
+            let mut unions = nested_unions(subtype, all_of).into_iter();
+            let mut fields = all_of_fields(subtype, all_of, &mut unions, spec);
+            fields.remove(&discriminator.property_name);
+            UnionVariant {
+                name: subtype.clone(),
+                field: HirField { ty: Ty::model(subtype), ..HirField::default() },
+                fields,
+                tag_value: Some(tag_value),
+            }
+        })
+        .collect();
+    if variants.is_empty() {
+        return None;
+    }
+    Some(Union {
+        name: name.to_string(),
+        variants,
+        docs: schema.description.as_ref().map(|d| Doc(d.clone())),
+        tag: Some(discriminator.property_name.clone()),
+        content: None,
+    })
+}
+
+fn is_internal(schema: &Schema) -> bool {
+    schema.data.extensions.get("x-internal").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+fn extension_str(schema: &Schema, name: &str) -> Option<String> {
+    schema.data.extensions.get(name).and_then(|v| v.as_str()).map(|s| s.to_string())
+}
+
+fn variant_name_for_ty(ty: &Ty, index: usize) -> String {
+    match ty {
+        Ty::String => "String".to_string(),
+        Ty::Integer { .. } | Ty::Unsigned { .. } => "Integer".to_string(),
+        Ty::Float => "Number".to_string(),
+        Ty::Boolean => "Boolean".to_string(),
+        Ty::Array(_) => "Array".to_string(),
+        Ty::Model(name) => name.clone(),
+        _ => format!("Variant{}", index),
+    }
+}
+
+/// A `oneOf` nested in an `allOf` becomes its own union, which the struct holds in a flattened field.
+struct NestedUnion<'a> {
+    field: String,
+    name: String,
+    schema: &'a Schema,
+    one_of: &'a [ReferenceOr<Schema>],
+}
+
+/// Numbered in the order their fields are merged, counting those in nested `allOf`s too, so every
+/// union gets its own name.
+fn nested_unions<'a>(name: &str, all_of: &'a [ReferenceOr<Schema>]) -> Vec<NestedUnion<'a>> {
+    let mut one_ofs = Vec::new();
+    collect_one_ofs(all_of, &mut one_ofs);
+    one_ofs.into_iter()
+        .enumerate()
+        .map(|(i, (schema, one_of))| {
+            let suffix = if i == 0 { String::new() } else { (i + 1).to_string() };
+            NestedUnion {
+                field: format!("variant{}", suffix),
+                name: format!("{}Variant{}", name, suffix),
+                schema,
+                one_of,
+            }
+        })
+        .collect()
+}
+
+fn collect_one_ofs<'a>(all_of: &'a [ReferenceOr<Schema>], one_ofs: &mut Vec<(&'a Schema, &'a [ReferenceOr<Schema>])>) {
+    for item in all_of.iter().filter_map(|s| s.as_item()) {
+        match &item.kind {
+            SchemaKind::OneOf { one_of } => one_ofs.push((item, one_of.as_slice())),
+            SchemaKind::AllOf { all_of } => collect_one_ofs(all_of, one_ofs),
+            _ => {}
+        }
+    }
+}
+
+/// Records that a schema needs besides its own, i.e. unions for `oneOf`s nested in an `allOf`.
+pub fn create_nested_records(name: &str, schema: &Schema, spec: &OpenAPI) -> Vec<Record> {
+    let SchemaKind::AllOf { all_of } = &schema.kind else {
+        return Vec::new();
+    };
+    nested_unions(name, all_of).into_iter()
+        .map(|u| Record::Union(create_union(u.name, u.one_of, u.schema, spec)))
+        .collect()
 }
 
 fn create_record_from_all_of(name: &str, all_of: &[ReferenceOr<Schema>], schema_data: &SchemaData, spec: &OpenAPI) -> Record {
+    let mut unions = nested_unions(name, all_of).into_iter();
+    let fields = all_of_fields(name, all_of, &mut unions, spec);
+    Record::Struct(Struct {
+        nullable: schema_data.nullable,
+        name: name.to_string(),
+        fields,
+        docs: schema_data.description.as_ref().map(|d| Doc(d.clone())),
+        dependent_required: extract_dependent_required(schema_data),
+    })
+}
+
+/// The fields of the struct `all_of` merges into. A nested `allOf` is merged in too, taking its unions
+/// from the same `unions`.
+fn all_of_fields<'a>(
+    name: &str,
+    all_of: &'a [ReferenceOr<Schema>],
+    unions: &mut impl Iterator<Item=NestedUnion<'a>>,
+    spec: &OpenAPI,
+) -> BTreeMap<String, HirField> {
     let mut fields = BTreeMap::new();
+    // A field required by any member is required in the merged struct.
+    let required: HashSet<&str> = all_of.iter()
+        .filter_map(|s| s.as_item())
+        .flat_map(required_names)
+        .map(|s| s.as_str())
+        .collect();
     for schema in all_of {
         match &schema {
+            ReferenceOr::Reference { .. } if schema.resolve(spec).data.discriminator.is_some() => {
+                // The base of a discriminated union is the union itself, so its properties are copied in
+                // instead of flattening it.
+                let base = schema.resolve(spec);
+                for (name, prop) in base.properties().into_iter().flatten() {
+                    let mut field = create_field(prop, spec);
+                    if !base.required(name) && !required.contains(name.as_str()) {
+                        field.optional = true;
+                    }
+                    fields.insert(name.to_string(), field);
+                }
+            }
             ReferenceOr::Reference { reference } => {
                 let schema_ref = SchemaReference::from_str(reference);
                 let name = extractor::get_name(schema_ref);
@@ -126,17 +398,30 @@ fn create_record_from_all_of(name: &str, all_of: &[ReferenceOr<Schema>], schema_
                 field.flatten = true;
                 fields.insert(name, field);
             }
+            ReferenceOr::Item(Schema { kind: SchemaKind::OneOf { .. }, .. }) => {
+                let union = unions.next().expect("nested_unions yields one union per nested oneOf");
+                fields.insert(union.field, HirField {
+                    ty: Ty::model(&union.name),
+                    flatten: true,
+                    ..HirField::default()
+                });
+            }
+            ReferenceOr::Item(Schema { kind: SchemaKind::AllOf { all_of }, .. }) => {
+                fields.extend(all_of_fields(name, all_of, unions, spec));
+            }
             ReferenceOr::Item(item) => {
                 match item.properties() {
                     Some(props) => {
                         for (name, schema) in props {
                             let mut field = create_field(schema, spec);
-                            if !item.required(name) {
+                            if !required.contains(name.as_str()) {
                                 field.optional = true;
                             }
                             fields.insert(name.to_string(), field);
                         }
                     }
+                    // A member that only lists `required` fields has nothing else to merge.
+                    None if !required_names(item).is_empty() => {}
                     None => {
                         warn!("Could not extract {} properties {:?}", name, item);
                     }
@@ -144,12 +429,15 @@ fn create_record_from_all_of(name: &str, all_of: &[ReferenceOr<Schema>], schema_
             }
         }
     }
-    Record::Struct(Struct {
-        nullable: schema_data.nullable,
-        name: name.to_string(),
-        fields,
-        docs: schema_data.description.as_ref().map(|d| Doc(d.clone())),
-    })
+    fields
+}
+
+fn required_names(schema: &Schema) -> &[String] {
+    match &schema.kind {
+        SchemaKind::Type(Type::Object(object)) => &object.required,
+        SchemaKind::Any(any) => &any.required,
+        _ => &[],
+    }
 }
 
 // records are data types: structs, newtypes
@@ -158,6 +446,9 @@ pub fn extract_records(spec: &OpenAPI, result: &mut HirSpec) -> Result<()> {
 
     spec.add_child_schemas(&mut schema_lookup);
     for (mut name, schema) in schema_lookup {
+        for rec in create_nested_records(&name, schema, spec) {
+            result.schemas.insert(rec.name().to_string(), rec);
+        }
         let rec = create_record(&name, schema, spec);
         let name = rec.name().to_string();
         result.schemas.insert(name, rec);
@@ -188,4 +479,172 @@ mod tests {
         assert_eq!(eye_color.optional, false);
         assert_eq!(weight.optional, true);
     }
+
+    #[test]
+    fn test_comma_separated_extension() {
+        let schema: Schema = serde_yaml::from_str(r#"
+type: object
+properties:
+  tags:
+    type: array
+    items:
+      type: string
+    x-comma-separated: true
+  ids:
+    type: array
+    items:
+      type: string
+"#).unwrap();
+        let spec = OpenAPI::default();
+        let hir::Record::Struct(rec) = crate::extractor::create_record("Filter", &schema, &spec) else { panic!() };
+        assert!(rec.fields["tags"].comma_separated);
+        assert!(!rec.fields["ids"].comma_separated);
+    }
+
+    #[test]
+    fn test_dependent_required() {
+        let schema: Schema = serde_yaml::from_str(r#"
+type: object
+properties:
+  card_number:
+    type: string
+  billing_address:
+    type: string
+x-dependent-required:
+  card_number: [billing_address]
+"#).unwrap();
+        let spec = OpenAPI::default();
+        let hir::Record::Struct(rec) = crate::extractor::create_record("Payment", &schema, &spec) else { panic!() };
+        assert_eq!(rec.dependent_required["card_number"], vec!["billing_address".to_string()]);
+    }
+
+    #[test]
+    fn test_internal_extension() {
+        let schema: Schema = serde_yaml::from_str(r#"
+type: object
+properties:
+  name:
+    type: string
+  debug_info:
+    type: string
+    x-internal: true
+"#).unwrap();
+        let spec = OpenAPI::default();
+        let hir::Record::Struct(rec) = crate::extractor::create_record("User", &schema, &spec) else { panic!() };
+        assert!(rec.fields["debug_info"].internal);
+        assert!(!rec.fields["name"].internal);
+    }
+
+    #[test]
+    fn test_discriminator_without_one_of() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths: {}
+components:
+  schemas:
+    Pet:
+      type: object
+      required: [petType, name]
+      properties:
+        petType:
+          type: string
+        name:
+          type: string
+      discriminator:
+        propertyName: petType
+        mapping:
+          cat: '#/components/schemas/Cat'
+    Cat:
+      allOf:
+        - $ref: '#/components/schemas/Pet'
+        - type: object
+          properties:
+            livesLeft:
+              type: integer
+    Dog:
+      allOf:
+        - $ref: '#/components/schemas/Pet'
+        - type: object
+          properties:
+            barks:
+              type: boolean
+"#).unwrap();
+        let mut result = hir::HirSpec::default();
+        crate::extractor::extract_records(&spec, &mut result).unwrap();
+        let hir::Record::Union(pet) = &result.schemas["Pet"] else { panic!() };
+        assert_eq!(pet.tag.as_deref(), Some("petType"));
+        let variants: Vec<_> = pet.variants.iter().map(|v| (v.name.as_str(), v.tag_value.as_deref())).collect();
+        assert_eq!(variants, vec![("Cat", Some("cat")), ("Dog", Some("Dog"))]);
+        // Subtypes get the base's fields directly, tag included, so they don't contain the union.
+        let hir::Record::Struct(cat) = &result.schemas["Cat"] else { panic!() };
+        assert!(!cat.fields["name"].optional);
+        assert!(!cat.fields["petType"].optional);
+        assert!(cat.fields.contains_key("livesLeft"));
+        assert!(!cat.fields.contains_key("Pet"));
+        // serde writes the tag of the union itself, so its variants leave it out.
+        let fields: Vec<_> = pet.variants[0].fields.keys().map(String::as_str).collect();
+        assert_eq!(fields, vec!["livesLeft", "name"]);
+    }
+
+    #[test]
+    fn test_all_of_required_union() {
+        let schema: Schema = serde_yaml::from_str(r#"
+allOf:
+  - type: object
+    properties:
+      id:
+        type: string
+      note:
+        type: string
+  - type: object
+    required: [id]
+    properties:
+      id:
+        type: string
+      name:
+        type: string
+  - required: [name]
+"#).unwrap();
+        let spec = OpenAPI::default();
+        let hir::Record::Struct(rec) = crate::extractor::create_record("Pet", &schema, &spec) else { panic!() };
+        assert!(!rec.fields["id"].optional);
+        assert!(!rec.fields["name"].optional);
+        assert!(rec.fields["note"].optional);
+    }
+
+    #[test]
+    fn test_if_then_required() {
+        let schema: Schema = serde_yaml::from_str(r#"
+type: object
+properties:
+  card_number:
+    type: string
+  billing_address:
+    type: string
+if:
+  required: [card_number]
+then:
+  required: [billing_address]
+"#).unwrap();
+        let spec = OpenAPI::default();
+        let hir::Record::Struct(rec) = crate::extractor::create_record("Payment", &schema, &spec) else { panic!() };
+        assert_eq!(rec.dependent_required["card_number"], vec!["billing_address".to_string()]);
+    }
+
+    #[test]
+    fn test_aliases_extension() {
+        let schema: Schema = serde_yaml::from_str(r#"
+type: object
+properties:
+  email:
+    type: string
+    x-aliases: [email_address, mail]
+"#).unwrap();
+        let spec = OpenAPI::default();
+        let hir::Record::Struct(rec) = crate::extractor::create_record("User", &schema, &spec) else { panic!() };
+        assert_eq!(rec.fields["email"].aliases, vec!["email_address".to_string(), "mail".to_string()]);
+    }
 }
\ No newline at end of file
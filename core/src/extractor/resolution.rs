@@ -19,7 +19,10 @@ pub fn schema_ref_to_ty_already_resolved(schema_ref: &ReferenceOr<Schema>, spec:
                 let r = oa::SchemaReference::from_str(reference);
                 match r {
                     SchemaReference::Schema { schema: s } => Ty::model(&s),
-                    SchemaReference::Property { schema: _, property: _ } => unimplemented!(),
+                    SchemaReference::Property { schema, property } => {
+                        warn!("References to properties are not supported ({}.{}). Defaulting to Any", schema, property);
+                        Ty::Any
+                    }
                 }
             }
             ReferenceOr::Item(schema) => schema_to_ty(schema, spec)
@@ -36,40 +39,73 @@ pub fn schema_to_ty(schema: &Schema, spec: &OpenAPI) -> Ty {
                 "decimal" => Ty::Currency {
                     serialization: hir::DecimalSerialization::String,
                 },
-                "integer" => Ty::Integer { serialization: hir::IntegerSerialization::String },
+                "integer" => Ty::Integer { serialization: hir::IntegerSerialization::String, bits: 64 },
                 "date" => Ty::Date {
                     serialization: hir::DateSerialization::Iso8601,
                 },
-                "date-time" => Ty::DateTime,
-                _ => Ty::String,
+                "date-time" => Ty::DateTime {
+                    serialization: hir::DateTimeSerialization::Iso8601,
+                },
+                "char" => Ty::Char,
+                "" if s.min_length == Some(1) && s.max_length == Some(1) => Ty::Char,
+                format => match hir::StringFormat::from_format(format) {
+                    Some(format) => Ty::Format(format),
+                    None => Ty::String,
+                },
             }
         }
         SchemaKind::Type(oa::Type::Number(_)) => Ty::Float,
-        SchemaKind::Type(oa::Type::Integer(_)) => {
+        SchemaKind::Type(oa::Type::Integer(integer)) => {
+            // Floats with multipleOf aren't checked, since the check would need a tolerance.
+            if let Some(multiple) = integer.multiple_of.filter(|&m| m > 1) {
+                return Ty::MultipleOf(multiple as u64);
+            }
             let null_as_zero = schema.data.extensions.get("x-null-as-zero")
                 .and_then(|v| v.as_bool()).unwrap_or(false);
             if null_as_zero {
-                return Ty::Integer { serialization: hir::IntegerSerialization::NullAsZero };
+                return Ty::Integer { serialization: hir::IntegerSerialization::NullAsZero, bits: 64 };
             }
             match schema.data.extensions.get("x-format").and_then(|s| s.as_str()) {
                 Some("date") => Ty::Date {
                     serialization: hir::DateSerialization::Integer,
                 },
-                _ => Ty::Integer { serialization: hir::IntegerSerialization::Simple },
+                Some("unix-timestamp") => Ty::DateTime {
+                    serialization: hir::DateTimeSerialization::UnixSeconds,
+                },
+                _ if integer.minimum.map_or(false, |m| m >= 0) || integer.format.as_str().starts_with("uint") => Ty::Unsigned {
+                    bits: integer_bits(integer.format.as_str()),
+                },
+                // Like the unsigned formats, `int8` through `int32` get their own width, and anything else is an `i64`.
+                _ => Ty::Integer {
+                    serialization: hir::IntegerSerialization::Simple,
+                    bits: integer_bits(integer.format.as_str()),
+                },
             }
         }
         SchemaKind::Type(oa::Type::Boolean {}) => Ty::Boolean,
-        SchemaKind::Type(oa::Type::Object(_)) => {
+        SchemaKind::Type(oa::Type::Object(object)) => {
             if let Some(title) = &schema.title {
                 Ty::model(&title)
+            } else if object.properties.is_empty() && (object.min_properties.is_some() || object.max_properties.is_some()) {
+                Ty::BoundedMap(hir::PropertyCount {
+                    min: object.min_properties.map(|n| n as u64),
+                    max: object.max_properties.map(|n| n as u64),
+                })
             } else {
                 Ty::Any
             }
         },
+        SchemaKind::Type(oa::Type::Array(_)) if schema.data.extensions.contains_key("prefixItems") => {
+            prefix_items_to_ty(schema, spec)
+        }
         SchemaKind::Type(oa::Type::Array(ArrayType {
                                              items: Some(item), ..
                                          })) => {
-            let inner = schema_ref_to_ty(&item, spec);
+            let mut inner = schema_ref_to_ty(&item, spec);
+            // Null items need an `Option` per element. Nullable referenced schemas are left as they are.
+            if item.as_item().map_or(false, |item| item.nullable) {
+                inner = Ty::Option(Box::new(inner));
+            }
             Ty::Array(Box::new(inner))
         }
         SchemaKind::Type(oa::Type::Array(ArrayType { items: None, .. })) => {
@@ -86,7 +122,10 @@ pub fn schema_to_ty(schema: &Schema, spec: &OpenAPI) -> Ty {
         }
         SchemaKind::OneOf { .. } => Ty::Any,
         SchemaKind::AnyOf { .. } => Ty::Any,
-        SchemaKind::Not { .. } => Ty::Any,
+        SchemaKind::Not { .. } => {
+            warn!("`not` schemas are not supported. Defaulting to Any");
+            Ty::Any
+        }
     }
 }
 
@@ -111,3 +150,268 @@ pub fn is_primitive(schema: &Schema, spec: &OpenAPI) -> bool {
         _ => false,
     }
 }
+
+
+/// The width named by an integer format like `int16` or `uint32`. Unknown formats are 64 bits.
+fn integer_bits(format: &str) -> u8 {
+    match format.trim_start_matches('u') {
+        "int8" => 8,
+        "int16" => 16,
+        "int32" => 32,
+        _ => 64,
+    }
+}
+
+/// Fails on a cycle of components that only refer to each other: plain `$ref`s, array items,
+/// or a single-member `allOf`. Resolving such a component never reaches an actual type, so it would
+/// recurse until the stack overflows. Cycles through object properties are fine, since those are models.
+pub fn check_ref_cycles(spec: &OpenAPI) -> anyhow::Result<()> {
+    fn target(schema_ref: &ReferenceOr<Schema>) -> Option<String> {
+        let reference = match schema_ref {
+            ReferenceOr::Reference { reference } => reference,
+            ReferenceOr::Item(schema) => match &schema.kind {
+                SchemaKind::Type(oa::Type::Array(ArrayType { items: Some(items), .. })) => items.as_ref_str()?,
+                SchemaKind::AllOf { all_of } if all_of.len() == 1 => all_of[0].as_ref_str()?,
+                _ => return None,
+            },
+        };
+        match SchemaReference::from_str(reference) {
+            SchemaReference::Schema { schema } => Some(schema),
+            SchemaReference::Property { .. } => None,
+        }
+    }
+
+    for start in spec.schemas.keys() {
+        let mut path = vec![start.clone()];
+        let mut current = start.clone();
+        while let Some(next) = spec.schemas.get(&current).and_then(target) {
+            if let Some(i) = path.iter().position(|name| *name == next) {
+                // Only report each cycle once, from its first component.
+                if i == 0 {
+                    path.push(next);
+                    return Err(anyhow::anyhow!("$ref cycle: {}", path.join(" -> ")));
+                }
+                break;
+            }
+            path.push(next.clone());
+            current = next;
+        }
+    }
+    Ok(())
+}
+
+/// 3.1 `prefixItems` describe a fixed-length array with a type per position.
+fn prefix_items_to_ty(schema: &Schema, spec: &OpenAPI) -> Ty {
+    let items = schema.data.extensions.get("prefixItems")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .map(|item| match serde_json::from_value::<ReferenceOr<Schema>>(item.clone()) {
+            Ok(item) => schema_ref_to_ty(&item, spec),
+            Err(e) => {
+                warn!("Invalid prefixItems schema: {}. Defaulting to Any", e);
+                Ty::Any
+            }
+        })
+        .collect();
+    Ty::Tuple(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use openapiv3::{OpenAPI, Schema};
+
+    use hir::{StringFormat, Ty};
+
+    use super::*;
+
+    fn ty_for(schema: &str) -> Ty {
+        let schema: Schema = serde_yaml::from_str(schema).unwrap();
+        schema_to_ty(&schema, &OpenAPI::default())
+    }
+
+    #[test]
+    fn test_ref_cycle() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Cycle
+  version: 1.0.0
+paths: {}
+components:
+  schemas:
+    Node:
+      type: object
+      properties:
+        children:
+          type: array
+          items:
+            $ref: '#/components/schemas/Node'
+    A:
+      type: array
+      items:
+        $ref: '#/components/schemas/B'
+    B:
+      $ref: '#/components/schemas/A'
+"#).unwrap();
+        let err = check_ref_cycles(&spec).unwrap_err();
+        assert_eq!(err.to_string(), "$ref cycle: A -> B -> A");
+    }
+
+    #[test]
+    fn test_ip_formats() {
+        assert!(matches!(ty_for("{type: string, format: ipv4}"), Ty::Format(StringFormat::Ipv4)));
+        assert!(matches!(ty_for("{type: string, format: ipv6}"), Ty::Format(StringFormat::Ipv6)));
+        assert!(matches!(ty_for("{type: string, format: hostname}"), Ty::Format(StringFormat::Hostname)));
+        assert!(matches!(ty_for("{type: string, format: json-pointer}"), Ty::Format(StringFormat::JsonPointer)));
+        assert!(matches!(ty_for("{type: string, format: relative-json-pointer}"), Ty::Format(StringFormat::RelativeJsonPointer)));
+        assert!(matches!(ty_for("{type: string, format: color}"), Ty::Format(StringFormat::Color)));
+        assert!(matches!(ty_for("{type: string, format: ulid}"), Ty::Format(StringFormat::Ulid)));
+        assert!(matches!(ty_for("{type: string, format: cuid}"), Ty::Format(StringFormat::Cuid)));
+        assert!(matches!(ty_for("{type: string, format: foo}"), Ty::String));
+        assert!(matches!(ty_for("{type: string, format: password}"), Ty::Format(StringFormat::Password)));
+    }
+
+    #[test]
+    fn test_unix_timestamp() {
+        let ty = ty_for("{type: integer, x-format: unix-timestamp}");
+        assert!(matches!(ty, Ty::DateTime { serialization: hir::DateTimeSerialization::UnixSeconds }));
+    }
+
+    #[test]
+    fn test_nullable_items() {
+        let Ty::Array(inner) = ty_for("{type: array, items: {type: string, nullable: true}}") else { panic!() };
+        let Ty::Option(inner) = *inner else { panic!("expected nullable items") };
+        assert!(matches!(*inner, Ty::String));
+        let Ty::Array(inner) = ty_for("{type: array, items: {type: string}}") else { panic!() };
+        assert!(matches!(*inner, Ty::String));
+    }
+
+    #[test]
+    fn test_char() {
+        assert!(matches!(ty_for("{type: string, format: char}"), Ty::Char));
+        assert!(matches!(ty_for("{type: string, minLength: 1, maxLength: 1}"), Ty::Char));
+        assert!(matches!(ty_for("{type: string, maxLength: 1}"), Ty::String));
+    }
+
+    #[test]
+    fn test_unsigned() {
+        assert!(matches!(ty_for("{type: integer, format: int64, minimum: 0}"), Ty::Unsigned { bits: 64 }));
+        assert!(matches!(ty_for("{type: integer, format: int32, minimum: 1}"), Ty::Unsigned { bits: 32 }));
+        assert!(matches!(ty_for("{type: integer, minimum: -1}"), Ty::Integer { .. }));
+    }
+
+    #[test]
+    fn test_integer_widths() {
+        assert!(matches!(ty_for("{type: integer, format: int8}"), Ty::Integer { bits: 8, .. }));
+        assert!(matches!(ty_for("{type: integer, format: int16}"), Ty::Integer { bits: 16, .. }));
+        assert!(matches!(ty_for("{type: integer, format: int32}"), Ty::Integer { bits: 32, .. }));
+        assert!(matches!(ty_for("{type: integer, format: int64}"), Ty::Integer { bits: 64, .. }));
+        assert!(matches!(ty_for("{type: integer}"), Ty::Integer { bits: 64, .. }));
+        assert!(matches!(ty_for("{type: integer, format: int128}"), Ty::Integer { bits: 64, .. }));
+        assert!(matches!(ty_for("{type: integer, format: uint32}"), Ty::Unsigned { bits: 32 }));
+        assert!(matches!(ty_for("{type: integer, format: uint8}"), Ty::Unsigned { bits: 8 }));
+        assert!(matches!(ty_for("{type: integer, format: int16, minimum: 0}"), Ty::Unsigned { bits: 16 }));
+    }
+
+    #[test]
+    fn test_bounded_map() {
+        let Ty::BoundedMap(count) = ty_for("{type: object, maxProperties: 3}") else { panic!() };
+        assert_eq!(count, hir::PropertyCount { min: None, max: Some(3) });
+        assert!(matches!(ty_for("{type: object}"), Ty::Any));
+    }
+
+    #[test]
+    fn test_multiple_of() {
+        assert!(matches!(ty_for("{type: integer, multipleOf: 5}"), Ty::MultipleOf(5)));
+        assert!(matches!(ty_for("{type: integer, multipleOf: 1}"), Ty::Integer { .. }));
+        assert!(matches!(ty_for("{type: number, multipleOf: 0.5}"), Ty::Float));
+    }
+
+    #[test]
+    fn test_prefix_items() {
+        let ty = ty_for("{type: array, prefixItems: [{type: string}, {type: integer}]}");
+        let Ty::Tuple(items) = ty else { panic!("expected a tuple, got {:?}", ty) };
+        assert!(matches!(items.as_slice(), [Ty::String, Ty::Integer { .. }]));
+    }
+
+    #[test]
+    fn test_unsupported_schema_falls_back_to_any() {
+        assert!(matches!(ty_for("{not: {type: string}}"), Ty::Any));
+
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Pet'
+components:
+  schemas:
+    Pet:
+      type: object
+      properties:
+        name:
+          type: string
+        anything_but_a_string:
+          not:
+            type: string
+"#).unwrap();
+        let spec = crate::extract_spec(&spec).unwrap();
+        let pet = spec.get_record("Pet").unwrap();
+        let field = pet.fields().find(|f| matches!(f.ty, Ty::Any));
+        assert!(field.is_some());
+    }
+
+    #[test]
+    fn test_property_reference_falls_back_to_any() {
+        let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /adoptions:
+    get:
+      operationId: getAdoption
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Adoption'
+components:
+  schemas:
+    Adoption:
+      type: object
+      properties:
+        owner:
+          $ref: '#/components/schemas/Pet/properties/owner'
+    Pet:
+      type: object
+      properties:
+        owner:
+          type: object
+          properties:
+            name:
+              type: string
+"#).unwrap();
+        let reference = ReferenceOr::Reference { reference: "#/components/schemas/Pet/properties/owner".to_string() };
+        assert!(matches!(schema_ref_to_ty(&reference, &spec), Ty::Any));
+
+        let spec = crate::extract_spec(&spec).unwrap();
+        let adoption = spec.get_record("Adoption").unwrap();
+        let owner = adoption.fields().next().unwrap();
+        assert!(matches!(owner.ty, Ty::Any));
+    }
+}
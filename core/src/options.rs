@@ -1,7 +1,8 @@
 use std::path::PathBuf;
 use convert_case::{Case, Casing};
 use mir::{literal, Literal};
-use hir::Language;
+use std::collections::{BTreeMap, BTreeSet};
+use hir::{Language, Operation, StringFormat};
 
 
 #[derive(Debug, Clone, Default)]
@@ -9,7 +10,92 @@ pub struct ConfigFlags {
     /// Only for Rust. Adds ormlite::TableMeta flags to the code.
     pub ormlite: bool,
     /// Only for Rust (for now). Adds fake::Dummy flags to the code.
-    pub fake: bool
+    pub fake: bool,
+    /// Only for Rust. Generates a unit struct for enums with a single value, instead of a one-variant enum.
+    pub single_variant_marker: bool,
+    /// Only for Rust. Generates a `validate()` method on structs with `dependentRequired` rules.
+    pub validate: bool,
+    /// Only for Rust. Defines each tag's operations on a trait (e.g. `UsersApi`) implemented by the client,
+    /// so the client can be swapped for a mock.
+    pub tag_traits: bool,
+    /// Only for Rust. Lets the client gzip request bodies over a size threshold, behind a `gzip` feature.
+    pub gzip_requests: bool,
+    /// Only for Rust. Makes `FluentRequest::into_request_builder` public, so callers can adjust a request before sending it.
+    pub request_builders: bool,
+    /// Only for Rust. Derives `PartialOrd` and `Ord` on string enums, ordering variants as the spec declares them.
+    pub ordered_enums: bool,
+    /// Only for Rust. Generates one crate-wide `ApiError` enum over the documented error schemas of all operations.
+    pub shared_errors: bool,
+    /// Only for Rust. Integers with `minimum >= 0` or a `uint*` format become `u8` to `u64` instead of signed.
+    pub unsigned_integers: bool,
+    /// Only for Rust. Maps with `minProperties`/`maxProperties` get a newtype that checks the property count.
+    pub bounded_maps: bool,
+    /// Only for Rust. Writes every model to `model.rs`, instead of a file per model under `model/`.
+    pub single_model_file: bool,
+    /// Only for Rust. Derives `schemars::JsonSchema` on models, behind a `jsonschema` feature.
+    pub jsonschema: bool,
+    /// Only for Rust. Leaves `x-internal` fields out of the models, instead of keeping them with `#[serde(skip)]`.
+    pub omit_internal_fields: bool,
+    /// Only for Rust. Client methods keep the casing of the `operationId`, instead of being snake_cased.
+    pub verbatim_method_names: bool,
+    /// Only for Rust. Strings that are always one character long become `char` instead of `String`.
+    pub char_strings: bool,
+    /// Only for Rust. Requests fail before sending if the client was given its token's scopes
+    /// and they don't include the operation's `x-required-scopes`.
+    pub check_scopes: bool,
+    /// Only for Rust. Requests get a `cancellable` method, behind a `cancellation` feature, that gives up
+    /// when a `tokio_util` `CancellationToken` is cancelled.
+    pub cancellation: bool,
+    /// Only for Rust. Operations with more than a few optional query parameters take them as one
+    /// `{Operation}Params` builder argument, instead of a setter per parameter on the request.
+    pub query_params_structs: bool,
+    /// Only for Rust. Generates an object-safe `DynClient` trait over every operation, so the client
+    /// can be stored as a `Box<dyn DynClient>` and swapped for another implementation.
+    pub dyn_client: bool,
+    /// Only for Rust. `ClientError` derives `thiserror::Error`, with an `#[error]` message per variant,
+    /// instead of hand-written `Display` and `Error` impls. Adds a `thiserror` dependency.
+    pub thiserror: bool,
+    /// Only for Rust. Requests get a `raw` method that skips parsing and returns the in-memory
+    /// response, so callers can borrow its body instead of decoding it into owned values.
+    pub raw_responses: bool,
+    /// Only for Rust. Builds `serde_json` with `preserve_order`, so `serde_json::Value` fields keep
+    /// object keys in the order they were parsed or inserted, e.g. for signing or diffing payloads.
+    pub preserve_order: bool,
+    /// Field that wraps every response body, e.g. `data`. Operations return the field's value directly.
+    pub response_envelope: Option<String>,
+    /// Prepended to every generated model name, e.g. to avoid collisions between several generated clients.
+    pub model_prefix: Option<String>,
+    /// Appended to every generated model name.
+    pub model_suffix: Option<String>,
+    /// Opt-in string formats (e.g. email, uuid) that get a validating newtype. Unlisted ones are plain strings.
+    pub strict_formats: BTreeSet<StringFormat>,
+    /// Client method names that replace the derived ones, keyed by `operationId` or by method and path,
+    /// e.g. `GET /users/{user_id}`. Names are used as given.
+    pub operation_renames: BTreeMap<String, String>,
+}
+
+impl ConfigFlags {
+    pub fn renames_models(&self) -> bool {
+        self.model_prefix.is_some() || self.model_suffix.is_some()
+    }
+
+    /// The model name with the configured prefix and suffix applied. Parts are space-separated
+    /// so that casing them later produces word boundaries, e.g. `Acme PetTag` becomes `AcmePetTag`.
+    pub fn model_name(&self, name: &str) -> String {
+        [self.model_prefix.as_deref(), Some(name), self.model_suffix.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The method name configured for an operation in `operation_renames`, if any.
+    pub fn operation_rename(&self, operation: &Operation) -> Option<&str> {
+        self.operation_renames.get(&operation.name)
+            .or_else(|| self.operation_renames.get(&format!("{} {}", operation.method.to_uppercase(), operation.path)))
+            .map(|s| s.as_str())
+    }
 }
 
 #[derive(Debug, Clone)]
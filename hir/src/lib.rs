@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 /// The API model.
 /// Higher level compared to code level models in ln-model.
 use std::fmt::{Debug, Formatter};
@@ -21,6 +21,13 @@ pub enum DateSerialization {
     Integer,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateTimeSerialization {
+    Iso8601,
+    /// Seconds since the epoch, from the `x-format: unix-timestamp` hint on integers.
+    UnixSeconds,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DecimalSerialization {
     String,
@@ -33,24 +40,134 @@ pub enum IntegerSerialization {
     NullAsZero,
 }
 
+/// String formats that get a dedicated type instead of a plain string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StringFormat {
+    Ipv4,
+    Ipv6,
+    Hostname,
+    Email,
+    Uuid,
+    Uri,
+    JsonPointer,
+    RelativeJsonPointer,
+    /// A plain string, but examples use a placeholder instead of something that looks like a real credential.
+    Password,
+    /// A regular expression, checked by compiling it.
+    Regex,
+    /// A hex color, `#RRGGBB` or `#RGB`.
+    Color,
+    /// A ULID: 26 characters of Crockford base32.
+    Ulid,
+    /// A CUID: `c` then 24 lowercase letters and digits.
+    Cuid,
+}
+
+impl StringFormat {
+    pub fn from_format(format: &str) -> Option<Self> {
+        match format {
+            "ipv4" => Some(StringFormat::Ipv4),
+            "ipv6" => Some(StringFormat::Ipv6),
+            "hostname" => Some(StringFormat::Hostname),
+            "email" => Some(StringFormat::Email),
+            "uuid" => Some(StringFormat::Uuid),
+            "uri" => Some(StringFormat::Uri),
+            "json-pointer" => Some(StringFormat::JsonPointer),
+            "relative-json-pointer" => Some(StringFormat::RelativeJsonPointer),
+            "password" => Some(StringFormat::Password),
+            "regex" => Some(StringFormat::Regex),
+            "color" => Some(StringFormat::Color),
+            "ulid" => Some(StringFormat::Ulid),
+            "cuid" => Some(StringFormat::Cuid),
+            _ => None,
+        }
+    }
+
+    /// The name of the format in the OpenAPI spec.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StringFormat::Ipv4 => "ipv4",
+            StringFormat::Ipv6 => "ipv6",
+            StringFormat::Hostname => "hostname",
+            StringFormat::Email => "email",
+            StringFormat::Uuid => "uuid",
+            StringFormat::Uri => "uri",
+            StringFormat::JsonPointer => "json-pointer",
+            StringFormat::RelativeJsonPointer => "relative-json-pointer",
+            StringFormat::Password => "password",
+            StringFormat::Regex => "regex",
+            StringFormat::Color => "color",
+            StringFormat::Ulid => "ulid",
+            StringFormat::Cuid => "cuid",
+        }
+    }
+
+    /// Whether the format is checked by a generated newtype, rather than using an existing type from the language.
+    pub fn is_validated(&self) -> bool {
+        match self {
+            StringFormat::Ipv4 => false,
+            StringFormat::Ipv6 => false,
+            StringFormat::Hostname => true,
+            StringFormat::Email => true,
+            StringFormat::Uuid => true,
+            StringFormat::Uri => true,
+            StringFormat::JsonPointer => true,
+            StringFormat::RelativeJsonPointer => true,
+            StringFormat::Password => false,
+            StringFormat::Regex => true,
+            StringFormat::Color => true,
+            StringFormat::Ulid => true,
+            StringFormat::Cuid => true,
+        }
+    }
+
+    /// Whether the format only gets a dedicated type when listed in the `strict_formats` option,
+    /// and is a plain string otherwise.
+    pub fn is_opt_in(&self) -> bool {
+        matches!(self, StringFormat::Email | StringFormat::Uuid | StringFormat::Uri)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Ty {
     String,
+    /// A string that's always one character long (`format: char`, or `minLength` and `maxLength` of 1).
+    Char,
     Integer {
         serialization: IntegerSerialization,
+        /// 8, 16 or 32 for `int8`/`int16`/`int32`, otherwise 64.
+        bits: u8,
     },
+    /// An integer with `minimum >= 0` or a `uint*` format, as a `u8` through `u64`.
+    Unsigned { bits: u8 },
     Float,
     Boolean,
     Array(Box<Ty>),
+    /// A value that can be null. Only used for array items, since fields and parameters have `optional`.
+    Option(Box<Ty>),
+    /// A fixed-length array with a type per position (`prefixItems`).
+    Tuple(Vec<Ty>),
     // OpenAPI name for the model. Hasn't been converted to a language type (e.g. cased, sanitized)
     Model(String),
     Unit,
     Date { serialization: DateSerialization },
-    DateTime,
+    DateTime { serialization: DateTimeSerialization },
     Currency { serialization: DecimalSerialization },
+    Format(StringFormat),
+    /// An integer that must be a multiple of the given value (`multipleOf`).
+    MultipleOf(u64),
+    /// A map of arbitrary values with `minProperties`/`maxProperties`.
+    BoundedMap(PropertyCount),
     Any,
 }
 
+/// Bounds on the number of properties of an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PropertyCount {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
 impl Default for Ty {
     fn default() -> Self {
         Ty::Any
@@ -61,17 +178,31 @@ impl Ty {
     pub fn integer() -> Self {
         Ty::Integer {
             serialization: IntegerSerialization::Simple,
+            bits: 64,
         }
     }
 
+    /// The first model this type refers to. Use `inner_models` for all of them, since a tuple can hold several.
     pub fn inner_model(&self) -> Option<&String> {
         match self {
             Ty::Model(name) => Some(name),
-            Ty::Array(ty) => ty.inner_model(),
+            Ty::Array(ty) | Ty::Option(ty) => ty.inner_model(),
+            Ty::Tuple(tys) => tys.iter().find_map(|ty| ty.inner_model()),
             _ => None,
         }
     }
 
+    /// Every model this type refers to, including each model in a tuple.
+    pub fn inner_models(&self) -> Vec<&String> {
+        let mut models = Vec::new();
+        self.visit(&mut |ty| {
+            if let Ty::Model(name) = ty {
+                models.push(name);
+            }
+        });
+        models
+    }
+
     pub fn is_iterable(&self) -> bool {
         self.inner_iterable().is_some()
     }
@@ -86,22 +217,49 @@ impl Ty {
     pub fn is_primitive(&self) -> bool {
         match self {
             Ty::String => true,
+            Ty::Char => true,
             Ty::Integer { .. } => true,
+            Ty::Unsigned { .. } => true,
             Ty::Float => true,
             Ty::Boolean => true,
             Ty::Array(_) => false,
+            Ty::Option(ty) => ty.is_primitive(),
+            Ty::Tuple(_) => false,
             Ty::Model(_) => false,
             Ty::Any => false,
             Ty::Unit => true,
             Ty::Date { .. } => true,
             Ty::Currency { .. } => true,
-            Ty::DateTime => true,
+            Ty::DateTime { .. } => true,
+            Ty::Format(_) => true,
+            Ty::MultipleOf(_) => true,
+            Ty::BoundedMap(_) => false,
         }
     }
 
     pub fn model(s: &str) -> Self {
         Ty::Model(s.to_string())
     }
+
+    /// Calls `f` on this type, then on every type nested in it.
+    pub fn visit<'a>(&'a self, f: &mut impl FnMut(&'a Ty)) {
+        f(self);
+        match self {
+            Ty::Array(ty) | Ty::Option(ty) => ty.visit(f),
+            Ty::Tuple(tys) => tys.iter().for_each(|ty| ty.visit(f)),
+            _ => {}
+        }
+    }
+
+    /// Calls `f` on this type, then on every type nested in it.
+    pub fn visit_mut(&mut self, f: &mut impl FnMut(&mut Ty)) {
+        f(self);
+        match self {
+            Ty::Array(ty) | Ty::Option(ty) => ty.visit_mut(f),
+            Ty::Tuple(tys) => tys.iter_mut().for_each(|ty| ty.visit_mut(f)),
+            _ => {}
+        }
+    }
 }
 
 /// Parameter is an input to an OpenAPI operation.
@@ -113,6 +271,13 @@ pub struct Parameter {
     pub optional: bool,
     pub doc: Option<Doc>,
     pub example: Option<serde_json::Value>,
+    /// Declared with `content: application/json`, so the value is sent as serialized JSON.
+    pub json_content: bool,
+    /// Sent when the caller doesn't set the parameter.
+    pub default: Option<serde_json::Value>,
+    /// How an array query parameter is written, when the spec says (`explode`). Otherwise the client's
+    /// `QueryArrayStyle` setting applies.
+    pub array_style: Option<ArrayStyle>,
 }
 
 impl Parameter {
@@ -132,6 +297,9 @@ impl Parameter {
             optional: false,
             doc: None,
             example: None,
+            json_content: false,
+            default: None,
+            array_style: None,
         }
     }
 }
@@ -157,6 +325,17 @@ impl From<&oa::Parameter> for Location {
     }
 }
 
+/// How an array query parameter is written to the query string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayStyle {
+    /// `ids=1&ids=2`
+    Repeat,
+    /// `ids[]=1&ids[]=2`
+    Brackets,
+    /// `ids=1,2`
+    Comma,
+}
+
 /// Specifically represents a parameter in Location::Query. We need special treatment for repeated keys.
 pub enum ParamKey {
     Key(String),
@@ -228,14 +407,26 @@ pub struct HirField {
     pub doc: Option<Doc>,
     pub example: Option<serde_json::Value>,
     pub flatten: bool,
+    /// Array field that is sent over the wire as a comma-joined string, e.g. "a,b,c".
+    pub comma_separated: bool,
+    /// Legacy names that are also accepted when deserializing, from `x-aliases`.
+    pub aliases: Vec<String>,
+    /// `readOnly`: assigned by the server, so clients don't set it.
+    pub read_only: bool,
+    /// `writeOnly`: sent by clients, but never returned by the server.
+    pub write_only: bool,
+    /// `x-internal`: not part of the public API.
+    pub internal: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Struct {
     pub name: String,
     pub nullable: bool,
     pub fields: BTreeMap<String, HirField>,
     pub docs: Option<Doc>,
+    /// `dependentRequired`: when the key field is present, the listed fields are required too.
+    pub dependent_required: BTreeMap<String, Vec<String>>,
 }
 
 #[derive(Debug, Clone)]
@@ -243,6 +434,8 @@ pub struct NewType {
     pub name: String,
     pub fields: Vec<HirField>,
     pub docs: Option<Doc>,
+    /// JSON type the value must not have, from a `not: {type: ...}` schema.
+    pub not: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -259,6 +452,45 @@ pub struct StrEnum {
     pub docs: Option<Doc>,
 }
 
+impl StrEnum {
+    /// The value of an enum that has exactly one (non-empty) variant.
+    pub fn single_variant(&self) -> Option<&str> {
+        let mut variants = self.variants.iter().filter(|v| !v.is_empty());
+        match (variants.next(), variants.next()) {
+            (Some(v), None) => Some(v.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// A `oneOf` schema: the value is one of the variants.
+#[derive(Debug, Clone, Default)]
+pub struct Union {
+    pub name: String,
+    pub variants: Vec<UnionVariant>,
+    pub docs: Option<Doc>,
+    /// Field holding the variant name (`x-serde-tag`). Without it, the union is untagged.
+    pub tag: Option<String>,
+    /// Field holding the variant's value (`x-serde-content`). Without it, a tagged union is internally tagged.
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UnionVariant {
+    pub name: String,
+    pub field: HirField,
+    /// Properties of an inline object variant, which becomes a struct variant. Empty for newtype variants.
+    pub fields: BTreeMap<String, HirField>,
+    /// Value of the tag field that selects the variant, when it comes from a `discriminator`.
+    pub tag_value: Option<String>,
+}
+
+impl UnionVariant {
+    pub fn is_struct(&self) -> bool {
+        !self.fields.is_empty()
+    }
+}
+
 /// an object type in the HIR
 #[derive(Debug, Clone)]
 pub enum Record {
@@ -266,6 +498,7 @@ pub enum Record {
     NewType(NewType),
     TypeAlias(String, HirField),
     Enum(StrEnum),
+    Union(Union),
 }
 
 impl Record {
@@ -275,6 +508,17 @@ impl Record {
             Record::Enum(e) => &e.name,
             Record::NewType(n) => &n.name,
             Record::TypeAlias(name, _) => name,
+            Record::Union(u) => &u.name,
+        }
+    }
+
+    fn set_name(&mut self, name: String) {
+        match self {
+            Record::Struct(s) => s.name = name,
+            Record::Enum(e) => e.name = name,
+            Record::NewType(n) => n.name = name,
+            Record::TypeAlias(n, _) => *n = name,
+            Record::Union(u) => u.name = name,
         }
     }
 
@@ -284,6 +528,7 @@ impl Record {
             Record::Enum(_) => 0,
             Record::NewType(n) => n.fields.len(),
             Record::TypeAlias(_, _) => 0,
+            Record::Union(u) => u.variants.len(),
         }
     }
 
@@ -293,6 +538,7 @@ impl Record {
             Record::Enum(_) => Box::new(empty()),
             Record::NewType(n) => Box::new(n.fields.iter()),
             Record::TypeAlias(_, f) => Box::new(once(f)),
+            Record::Union(u) => Box::new(u.variants.iter().flat_map(|v| once(&v.field).chain(v.fields.values()))),
         }
     }
 
@@ -302,6 +548,7 @@ impl Record {
             Record::Enum(_) => Box::new(empty()),
             Record::NewType(n) => Box::new(n.fields.iter_mut()),
             Record::TypeAlias(_, f) => Box::new(once(f)),
+            Record::Union(u) => Box::new(u.variants.iter_mut().flat_map(|v| once(&mut v.field).chain(v.fields.values_mut()))),
         }
     }
 
@@ -318,6 +565,7 @@ impl Record {
             Record::Enum(_) => false,
             Record::NewType(_) => false,
             Record::TypeAlias(_, f) => f.optional,
+            Record::Union(_) => false,
         }
     }
 }
@@ -331,6 +579,22 @@ pub struct HirSpec {
     pub security: Vec<AuthStrategy>,
 
     pub api_docs_url: Option<String>,
+
+    pub webhooks: Vec<Webhook>,
+
+    /// `info.title` and `info.version`, which clients identify themselves with.
+    pub title: String,
+    pub version: String,
+}
+
+/// An inbound event described in the OpenAPI 3.1 `webhooks` section.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    /// Name of the webhook in the spec, not converted to a language ident.
+    pub name: String,
+    pub doc: Option<Doc>,
+    /// The payload sent with the webhook.
+    pub ty: Ty,
 }
 
 pub enum ServerStrategy {
@@ -357,6 +621,109 @@ pub fn qualified_env_var(service: &str, var_name: &str) -> String {
 }
 
 impl HirSpec {
+    /// Rename every model, along with every reference to it.
+    pub fn rename_models(&mut self, rename: impl Fn(&str) -> String) {
+        let schemas = std::mem::take(&mut self.schemas);
+        self.schemas = schemas.into_iter().map(|(name, mut record)| {
+            let name = rename(&name);
+            record.set_name(rename(record.name()));
+            (name, record)
+        }).collect();
+        self.visit_tys_mut(|ty| {
+            if let Ty::Model(model) = ty {
+                *model = rename(model);
+            }
+        });
+    }
+
+    /// Calls `f` on every type in the spec, including types nested in arrays and tuples.
+    pub fn visit_tys<'a>(&'a self, mut f: impl FnMut(&'a Ty)) {
+        for record in self.schemas.values() {
+            for field in record.fields() {
+                field.ty.visit(&mut f);
+            }
+        }
+        for operation in self.operations.iter() {
+            operation.ret.visit(&mut f);
+            for param in operation.parameters.iter() {
+                param.ty.visit(&mut f);
+            }
+            for error in operation.errors.iter() {
+                error.ty.visit(&mut f);
+            }
+        }
+        for webhook in self.webhooks.iter() {
+            webhook.ty.visit(&mut f);
+        }
+    }
+
+    /// Calls `f` on every type in the spec, including types nested in arrays and tuples.
+    pub fn visit_tys_mut(&mut self, mut f: impl FnMut(&mut Ty)) {
+        for record in self.schemas.values_mut() {
+            for field in record.fields_mut() {
+                field.ty.visit_mut(&mut f);
+            }
+        }
+        for operation in self.operations.iter_mut() {
+            operation.ret.visit_mut(&mut f);
+            for param in operation.parameters.iter_mut() {
+                param.ty.visit_mut(&mut f);
+            }
+            for error in operation.errors.iter_mut() {
+                error.ty.visit_mut(&mut f);
+            }
+        }
+        for webhook in self.webhooks.iter_mut() {
+            webhook.ty.visit_mut(&mut f);
+        }
+    }
+
+    /// Makes operations whose response model has a required `field` return that field's type instead.
+    pub fn unwrap_envelopes(&mut self, field: &str) {
+        for operation in self.operations.iter_mut() {
+            let Ty::Model(name) = &operation.ret else {
+                continue;
+            };
+            let Some(Record::Struct(envelope)) = self.schemas.get(name) else {
+                continue;
+            };
+            let Some(inner) = envelope.fields.get(field).filter(|f| !f.optional) else {
+                continue;
+            };
+            operation.ret = inner.ty.clone();
+            operation.envelope = Some(field.to_string());
+        }
+    }
+
+    /// Drops `x-internal` fields from every struct.
+    pub fn remove_internal_fields(&mut self) {
+        for record in self.schemas.values_mut() {
+            if let Record::Struct(s) = record {
+                s.fields.retain(|_, f| !f.internal);
+            }
+        }
+    }
+
+    /// Turns string formats that `keep` rejects back into plain strings.
+    pub fn downgrade_formats(&mut self, keep: impl Fn(StringFormat) -> bool) {
+        self.visit_tys_mut(|ty| {
+            if let Ty::Format(format) = ty {
+                if !keep(*format) {
+                    *ty = Ty::String;
+                }
+            }
+        });
+    }
+
+    /// Models that appear as an error response of any operation.
+    pub fn error_models(&self) -> BTreeSet<&str> {
+        self.operations.iter()
+            .flat_map(|o| o.errors.iter())
+            .flat_map(|e| e.ty.inner_models())
+            .map(|s| s.as_str())
+            .collect()
+    }
+
     pub fn get_record(&self, name: &str) -> Result<&Record> {
         self.schemas.get(name).ok_or_else(|| anyhow::anyhow!("No record named {}", name))
     }
@@ -427,6 +794,35 @@ pub struct Operation {
     pub ret: Ty,
     pub path: String,
     pub method: String,
+    pub tags: Vec<String>,
+    /// Media types of the success response, in preference order.
+    pub response_media_types: Vec<String>,
+    /// Operation- or path-level server (operation wins), which overrides the client's base url for this operation.
+    pub server: Option<String>,
+    /// Documented error responses with a json body.
+    pub errors: Vec<ErrorResponse>,
+    /// Field of the response body that holds the return value, when responses are wrapped in an envelope.
+    pub envelope: Option<String>,
+    /// OAuth2 scopes the operation needs (`x-required-scopes`).
+    pub scopes: Vec<String>,
+    /// The body is a JSON merge patch, sent as `application/merge-patch+json`.
+    pub merge_patch: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrorResponse {
+    pub status: ErrorStatus,
+    pub ty: Ty,
+}
+
+/// The statuses a documented response applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorStatus {
+    Code(u16),
+    /// A range like `4XX`, stored as its first digit.
+    Range(u16),
+    /// The `default` response, for any status not documented otherwise.
+    Default,
 }
 
 impl Operation {
@@ -447,10 +843,19 @@ impl Operation {
         format!("{}Required", self.name)
     }
 
+    pub fn params_struct_name(&self) -> String {
+        format!("{}Params", self.name)
+    }
+
     pub fn crowded_args(&self) -> bool {
         self.parameters.iter().filter(|p| !p.optional).count() > 3
     }
 
+    /// HEAD and OPTIONS responses carry no body, only a status and headers.
+    pub fn headers_only(&self) -> bool {
+        matches!(self.method.as_str(), "head" | "options")
+    }
+
     pub fn has_response(&self) -> bool {
         !matches!(self.ret, Ty::Unit)
     }
@@ -493,6 +898,9 @@ impl Operation {
                     optional: false,
                     doc: None,
                     example: None,
+                    json_content: false,
+                    default: None,
+                    array_style: None,
                 }]
             }
             _ if self.use_required_struct(generator) => {
@@ -503,6 +911,9 @@ impl Operation {
                     optional: false,
                     doc: None,
                     example: None,
+                    json_content: false,
+                    default: None,
+                    array_style: None,
                 }]
             }
             _ => {
@@ -536,6 +947,7 @@ impl Operation {
             name: self.required_struct_name(),
             fields,
             docs: None,
+            dependent_required: BTreeMap::new(),
         }
     }
 }
@@ -549,6 +961,13 @@ impl Default for Operation {
             ret: Ty::Unit,
             path: "".to_string(),
             method: "".to_string(),
+            tags: Vec::new(),
+            response_media_types: Vec::new(),
+            server: None,
+            errors: Vec::new(),
+            envelope: None,
+            scopes: Vec::new(),
+            merge_patch: false,
         }
     }
 }
@@ -561,6 +980,11 @@ impl From<&Parameter> for HirField {
             doc: p.doc.clone(),
             example: p.example.clone(),
             flatten: false,
+            comma_separated: false,
+            aliases: Vec::new(),
+            read_only: false,
+            write_only: false,
+            internal: false,
         }
     }
 }
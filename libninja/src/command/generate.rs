@@ -1,11 +1,12 @@
 use std::path::{Path, PathBuf};
 use std::process::Output;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::{Args, ValueEnum};
 use convert_case::{Case, Casing};
 use tracing::debug;
-use crate::{OutputConfig, Language, PackageConfig, read_spec, generate_library};
+use crate::{OutputConfig, Language, PackageConfig, read_spec, generate_library, crate_name_and_version};
 use ln_core::{ConfigFlags};
+use hir::StringFormat;
 
 #[derive(ValueEnum, Debug, Clone, Copy)]
 pub enum Config {
@@ -13,6 +14,48 @@ pub enum Config {
     Ormlite,
     /// Only used by Rust (for now). Adds fake::Dummy flags to the code.
     Fake,
+    /// Only used by Rust. Generates a unit struct for enums with a single value.
+    SingleVariantMarker,
+    /// Only used by Rust. Generates `validate()` methods that check `dependentRequired` rules.
+    Validate,
+    /// Only used by Rust. Defines each tag's operations on a trait implemented by the client.
+    TagTraits,
+    /// Only used by Rust. Lets the client gzip large request bodies, behind a `gzip` feature.
+    GzipRequests,
+    /// Only used by Rust. Exposes the built-but-unsent request for each operation.
+    RequestBuilders,
+    /// Only used by Rust. Derives `Ord` on string enums, in the spec's declaration order.
+    OrderedEnums,
+    /// Only used by Rust. Generates a single `ApiError` enum covering every operation's documented errors.
+    SharedErrors,
+    /// Only used by Rust. Maps integers with `minimum >= 0` to unsigned types.
+    UnsignedIntegers,
+    /// Only used by Rust. Adds schemars::JsonSchema derives behind a `jsonschema` feature.
+    Jsonschema,
+    /// Only used by Rust. Checks `minProperties`/`maxProperties` with a validating map newtype.
+    BoundedMaps,
+    /// Only used by Rust. Writes all models to a single `model.rs`.
+    SingleModelFile,
+    /// Only used by Rust. Leaves `x-internal` fields out of the models entirely.
+    OmitInternalFields,
+    /// Only used by Rust. Keeps `operationId` casing for client method names.
+    VerbatimMethodNames,
+    /// Only used by Rust. Maps single-character strings (`format: char`, or a length of exactly 1) to `char`.
+    CharStrings,
+    /// Only used by Rust. Checks `x-required-scopes` against the scopes given to the client.
+    CheckScopes,
+    /// Only used by Rust. Lets requests be cancelled with a `CancellationToken`, behind a `cancellation` feature.
+    Cancellation,
+    /// Only used by Rust. Passes crowded optional query parameters as a `{Operation}Params` builder.
+    QueryParamsStructs,
+    /// Only used by Rust. Generates an object-safe `DynClient` trait implemented by the client.
+    DynClient,
+    /// Only used by Rust. Derives the client error's `Display` and `Error` impls with `thiserror`.
+    Thiserror,
+    /// Only used by Rust. Adds a `raw` method to requests that returns the unparsed response.
+    RawResponses,
+    /// Only used by Rust. Enables serde_json's `preserve_order`, so untyped JSON keeps its key order.
+    PreserveOrder,
 }
 
 fn build_config(configs: &[Config]) -> ConfigFlags {
@@ -21,6 +64,27 @@ fn build_config(configs: &[Config]) -> ConfigFlags {
         match c {
             Config::Ormlite => config.ormlite = true,
             Config::Fake => config.fake = true,
+            Config::SingleVariantMarker => config.single_variant_marker = true,
+            Config::Validate => config.validate = true,
+            Config::TagTraits => config.tag_traits = true,
+            Config::GzipRequests => config.gzip_requests = true,
+            Config::RequestBuilders => config.request_builders = true,
+            Config::OrderedEnums => config.ordered_enums = true,
+            Config::SharedErrors => config.shared_errors = true,
+            Config::UnsignedIntegers => config.unsigned_integers = true,
+            Config::Jsonschema => config.jsonschema = true,
+            Config::BoundedMaps => config.bounded_maps = true,
+            Config::SingleModelFile => config.single_model_file = true,
+            Config::OmitInternalFields => config.omit_internal_fields = true,
+            Config::VerbatimMethodNames => config.verbatim_method_names = true,
+            Config::CharStrings => config.char_strings = true,
+            Config::CheckScopes => config.check_scopes = true,
+            Config::Cancellation => config.cancellation = true,
+            Config::QueryParamsStructs => config.query_params_structs = true,
+            Config::DynClient => config.dyn_client = true,
+            Config::Thiserror => config.thiserror = true,
+            Config::RawResponses => config.raw_responses = true,
+            Config::PreserveOrder => config.preserve_order = true,
         }
     }
     config
@@ -47,11 +111,32 @@ pub struct Generate {
     #[clap(short, long)]
     config: Vec<Config>,
 
+    /// Prefix for generated model names.
+    #[clap(long)]
+    model_prefix: Option<String>,
+
+    /// Suffix for generated model names.
+    #[clap(long)]
+    model_suffix: Option<String>,
+
+    /// String formats to generate validating newtypes for, e.g. `email,uuid`.
+    #[clap(long, value_delimiter = ',')]
+    strict_formats: Vec<String>,
+
+    /// Field that wraps response bodies, e.g. `data`. Operations return its value directly.
+    #[clap(long)]
+    response_envelope: Option<String>,
+
+    /// Client method names to use instead of the derived ones, e.g. `getV1UsersUserId=get_user`.
+    /// Operations are keyed by `operationId`, or by method and path (`GET /users/{id}=get_user`).
+    #[clap(long = "rename-operation", value_delimiter = ',')]
+    rename_operations: Vec<String>,
+
     /// Repo (e.g. libninjacom/plaid-rs)
     #[clap(long)]
     repo: Option<String>,
 
-    /// Package name. Defaults to the service name.
+    /// Package name. Defaults to the spec's `x-rust-crate-name`, then the service name.
     #[clap(short, long = "package")]
     package_name: Option<String>,
 
@@ -64,20 +149,31 @@ pub struct Generate {
 
 impl Generate {
     pub fn run(self) -> Result<()> {
-        let package_name = self.package_name.unwrap_or_else(|| self.name.to_lowercase());
-
         let path = PathBuf::from(self.spec_filepath);
         let output_dir = self.output_dir.unwrap_or_else(|| ".".to_string());
         let spec = read_spec(&path)?;
+        let (package_name, version) = crate_name_and_version(&spec, &self.name, self.package_name, self.version);
+        let mut config = build_config(&self.config);
+        config.model_prefix = self.model_prefix;
+        config.model_suffix = self.model_suffix;
+        config.response_envelope = self.response_envelope;
+        config.operation_renames = self.rename_operations.iter()
+            .map(|r| r.split_once('=')
+                .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                .ok_or_else(|| anyhow!("Operation rename must look like `operation=method_name`: {}", r)))
+            .collect::<Result<_>>()?;
+        config.strict_formats = self.strict_formats.iter()
+            .map(|f| StringFormat::from_format(f).ok_or_else(|| anyhow!("Unknown string format: {}", f)))
+            .collect::<Result<_>>()?;
         generate_library(spec, OutputConfig {
             dest_path: PathBuf::from(output_dir),
-            config: build_config(&self.config),
+            config,
             language: self.language,
             build_examples: self.examples.unwrap_or(true),
             package_name,
             service_name: self.name.to_case(Case::Pascal),
             github_repo: self.repo,
-            version: self.version,
+            version,
         })
     }
 }
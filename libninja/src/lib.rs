@@ -39,6 +39,26 @@ pub fn read_spec(path: &Path) -> Result<OpenAPI> {
     Ok(openapi)
 }
 
+/// A string extension on the spec's `info`, e.g. `x-rust-crate-name`.
+pub fn info_extension(spec: &OpenAPI, key: &str) -> Option<String> {
+    spec.info.extensions.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
+}
+
+/// The crate name and version to generate. The command line wins, then the spec's `x-rust-crate-name`
+/// and `x-rust-crate-version`. The name falls back to the lowercased service name.
+pub fn crate_name_and_version(
+    spec: &OpenAPI,
+    service_name: &str,
+    package_name: Option<String>,
+    version: Option<String>,
+) -> (String, Option<String>) {
+    let package_name = package_name
+        .or_else(|| info_extension(spec, "x-rust-crate-name"))
+        .unwrap_or_else(|| service_name.to_lowercase());
+    let version = version.or_else(|| info_extension(spec, "x-rust-crate-version"));
+    (package_name, version)
+}
+
 pub fn generate_library(spec: OpenAPI, opts: OutputConfig) -> Result<()> {
     match opts.language {
         Language::Rust => rust::generate_rust_library(spec, opts),
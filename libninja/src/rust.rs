@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::hash::Hash;
 use std::path::Path;
 use std::thread::current;
@@ -19,25 +19,31 @@ use format::format_code;
 use ln_core::{copy_builtin_files, copy_builtin_templates, create_context, get_template_file, prepare_templates};
 use ::mir::{Visibility, Import, File};
 use ln_core::fs;
-use hir::{HirSpec, IntegerSerialization, DateSerialization, Location, Parameter, AuthStrategy, Oauth2Auth, qualified_env_var};
+use hir::{HirSpec, PropertyCount, Ty, IntegerSerialization, DateSerialization, Location, Parameter, AuthStrategy, Oauth2Auth, qualified_env_var, StringFormat};
 use mir::Ident;
 
 use crate::{add_operation_models, extract_spec, PackageConfig, OutputConfig};
-use crate::rust::client::{build_Client_authenticate, server_url};
+use crate::rust::client::build_Client_authenticate;
 pub use crate::rust::codegen::generate_example;
 use crate::rust::codegen::{codegen_function, sanitize_filename, ToRustCode};
 use crate::rust::io::write_rust_file_to_path;
-use crate::rust::lower_mir::{generate_model_rs, generate_single_model_file};
-use crate::rust::request::{assign_inputs_to_request, build_request_struct, build_request_struct_builder_methods, build_url, generate_request_model_rs};
+use crate::rust::lower_mir::{generate_combined_model_rs, generate_model_rs, generate_single_model_file};
+use crate::rust::request::{build_request_struct, build_request_struct_builder_methods, generate_request_model_rs};
 
 pub mod client;
 pub mod codegen;
 pub mod format;
 pub mod lower_mir;
 pub mod request;
+pub mod webhook;
+pub mod validated;
+pub mod error;
+pub mod compression;
 mod io;
 mod serde;
 mod cargo_toml;
+#[cfg(test)]
+mod test_util;
 
 #[derive(Debug)]
 pub struct Extras {
@@ -48,35 +54,65 @@ pub struct Extras {
     integer_date_serialization: bool,
     basic_auth: bool,
     oauth2: bool,
+    comma_separated: bool,
+    /// Formats that need a newtype in the generated validated module.
+    validated_formats: BTreeSet<StringFormat>,
+    /// `multipleOf` values that need a newtype in the generated validated module.
+    multiples: BTreeSet<u64>,
+    /// `minProperties`/`maxProperties` bounds that need a newtype in the generated validated module.
+    bounded_maps: BTreeSet<PropertyCount>,
 }
 
 impl Extras {
     pub fn needs_serde(&self) -> bool {
-        self.null_as_zero || self.date_serialization
+        self.null_as_zero || self.date_serialization || self.comma_separated
+    }
+
+    pub fn needs_validated(&self) -> bool {
+        !self.validated_formats.is_empty() || !self.multiples.is_empty() || !self.bounded_maps.is_empty()
     }
 }
 
 pub fn calculate_extras(spec: &HirSpec) -> Extras {
-    use hir::Ty;
     let mut null_as_zero = false;
     let mut date_serialization = false;
     let mut currency = false;
     let mut integer_date_serialization = false;
     let mut option_i64_str = false;
+    let mut comma_separated = false;
+    let mut validated_formats = BTreeSet::new();
+    let mut multiples = BTreeSet::new();
+    let mut bounded_maps = BTreeSet::new();
+    // Responses, errors and webhooks can use validated types too, as can any element of a tuple.
+    spec.visit_tys(|ty| match ty {
+        Ty::Format(format) if format.is_validated() => {
+            validated_formats.insert(*format);
+        }
+        Ty::MultipleOf(multiple) => {
+            multiples.insert(*multiple);
+        }
+        Ty::BoundedMap(count) => {
+            bounded_maps.insert(*count);
+        }
+        _ => {}
+    });
     for (_, record) in &spec.schemas {
         for field in record.fields() {
+            if field.comma_separated {
+                comma_separated = true;
+            }
             match &field.ty {
-                Ty::Integer { serialization: IntegerSerialization::NullAsZero } => {
+                Ty::Integer { serialization: IntegerSerialization::NullAsZero, .. } => {
                     null_as_zero = true;
                 }
-                Ty::Integer { serialization: IntegerSerialization::String } => {
+                Ty::Integer { serialization: IntegerSerialization::String, .. } => {
                     option_i64_str = true;
                 }
                 Ty::Date { serialization: DateSerialization::Integer } => {
                     integer_date_serialization = true;
                     date_serialization = true;
                 }
-                Ty::DateTime => {
+                Ty::DateTime { .. } => {
                     date_serialization = true;
                 }
                 Ty::Currency { .. } => {
@@ -96,6 +132,10 @@ pub fn calculate_extras(spec: &HirSpec) -> Extras {
         option_i64_str,
         basic_auth,
         oauth2,
+        comma_separated,
+        validated_formats,
+        multiples,
+        bounded_maps,
     }
 }
 
@@ -125,7 +165,39 @@ pub fn generate_rust_library(spec: OpenAPI, opts: OutputConfig) -> Result<()> {
     let src_path = opts.dest_path.join("src");
 
     // Prepare the HIR Spec.
-    let spec = extract_spec(&spec)?;
+    let mut spec = extract_spec(&spec)?;
+    if opts.config.renames_models() {
+        spec.rename_models(|name| opts.config.model_name(name));
+    }
+    if opts.config.omit_internal_fields {
+        spec.remove_internal_fields();
+    }
+    if let Some(field) = &opts.config.response_envelope {
+        spec.unwrap_envelopes(field);
+    }
+    spec.downgrade_formats(|format| !format.is_opt_in() || opts.config.strict_formats.contains(&format));
+    if !opts.config.unsigned_integers {
+        spec.visit_tys_mut(|ty| {
+            if let Ty::Unsigned { .. } = ty {
+                *ty = Ty::integer();
+            }
+        });
+    }
+    if !opts.config.char_strings {
+        spec.visit_tys_mut(|ty| {
+            if let Ty::Char = ty {
+                *ty = Ty::String;
+            }
+        });
+    }
+    if !opts.config.bounded_maps {
+        spec.visit_tys_mut(|ty| {
+            if let Ty::BoundedMap(_) = ty {
+                *ty = Ty::Any;
+            }
+        });
+    }
+    client::check_method_names(&spec, &opts.config)?;
     let extras = calculate_extras(&spec);
 
     // if src doesn't exist that's fine
@@ -159,8 +231,12 @@ pub fn generate_rust_library(spec: OpenAPI, opts: OutputConfig) -> Result<()> {
     };
     write_model_module(&spec, &opts)?;
     write_request_module(&spec, &opts)?;
+    write_error_module(&spec, &opts)?;
+    write_compression_module_if_needed(&opts)?;
+    write_webhook_module(&spec, &opts)?;
     write_lib_rs(&spec, &extras, &opts)?;
     write_serde_module_if_needed(&extras, &opts.dest)?;
+    write_validated_module_if_needed(&extras, &opts.dest)?;
 
     let spec = add_operation_models(opts.language, spec)?;
 
@@ -218,6 +294,10 @@ fn write_model_module(spec: &HirSpec, opts: &PackageConfig) -> Result<()> {
     let config = &opts.config;
     let src_path = opts.dest.join("src");
 
+    if config.single_model_file {
+        let model_rs = generate_combined_model_rs(spec, config);
+        return write_rust_file_to_path(&src_path.join("model.rs"), model_rs);
+    }
     let model_rs = generate_model_rs(spec, config);
     write_rust_file_to_path(&src_path.join("model.rs"), model_rs)?;
     fs::create_dir_all(src_path.join("model"))?;
@@ -230,14 +310,12 @@ fn write_model_module(spec: &HirSpec, opts: &PackageConfig) -> Result<()> {
     Ok(())
 }
 
-fn static_shared_http_client(spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
-    let url = server_url(spec, opt);
+fn static_shared_http_client() -> TokenStream {
     quote! {
         static SHARED_HTTPCLIENT: OnceLock<httpclient::Client> = OnceLock::new();
 
         pub fn default_http_client() -> httpclient::Client {
-            httpclient::Client::new()
-                .base_url(#url)
+            ClientConfig::default().http_client()
         }
 
         /// Use this method if you want to add custom middleware to the httpclient.
@@ -298,6 +376,8 @@ fn write_lib_rs(spec: &HirSpec, extras: &Extras, opts: &PackageConfig) -> Result
     let name = &opts.service_name;
     let mut struct_Client = client::struct_Client(spec, &opts);
     let impl_Client = client::impl_Client(spec, &opts);
+    let tag_traits = client::tag_traits(spec, &opts);
+    let dyn_client = client::dyn_client(spec, &opts);
 
     let client_name = struct_Client.name.clone();
     let lib_rs_template = opts.get_file_template("src/lib.rs").unwrap_or_else(|| {
@@ -347,7 +427,11 @@ fn write_lib_rs(spec: &HirSpec, extras: &Extras, opts: &PackageConfig) -> Result
             #impl_ServiceAuthentication
         }
     }).unwrap_or_default();
-    let static_shared_http_client = static_shared_http_client(spec, opts);
+    let static_shared_http_client = static_shared_http_client();
+    let client_config = client::client_config(spec, opts);
+    let request_signer = client::request_signer_trait();
+    let query_array_style = client::query_array_style_enum();
+    let user_agent = client::user_agent_const(spec, &opts);
     let oauth = spec.security.iter().filter_map(|s| match s {
         AuthStrategy::OAuth2(auth) => Some(auth),
         _ => None,
@@ -356,14 +440,44 @@ fn write_lib_rs(spec: &HirSpec, extras: &Extras, opts: &PackageConfig) -> Result
         shared_oauth2_flow(auth, spec, opts)
     }).unwrap_or_default();
 
+    let validated = extras.needs_validated().then(|| {
+        quote! {
+            pub mod validated;
+        }
+    }).unwrap_or_default();
+
+    let compression = opts.config.gzip_requests.then(|| {
+        quote! {
+            #[cfg(feature = "gzip")]
+            mod compression;
+        }
+    }).unwrap_or_default();
+
+    let webhook = (!spec.webhooks.is_empty()).then(|| {
+        quote! {
+            pub mod webhook;
+        }
+    }).unwrap_or_default();
+
     let code = quote! {
         #base64_import
         #serde
+        #validated
+        #webhook
+        #compression
+        pub mod error;
+        pub use error::*;
         #static_shared_http_client
+        #client_config
+        #request_signer
+        #query_array_style
+        #user_agent
         #shared_oauth2_flow
         #fluent_request
         #struct_Client
         #impl_Client
+        #tag_traits
+        #dyn_client
         #security
     };
     io::write_rust_to_path(&src_path.join("lib.rs"), code, &lib_rs_template)?;
@@ -377,31 +491,22 @@ fn write_request_module(spec: &HirSpec, opts: &PackageConfig) -> Result<()> {
     fs::create_dir_all(src_path.join("request"))?;
     let mut modules = vec![];
 
-    let authenticate = spec.has_security()
-        .then(|| quote! {
-                r = self.client.authenticate(r);
-            }).unwrap_or_default();
-
     for operation in &spec.operations {
         let fname = operation.file_name();
         let request_structs = build_request_struct(operation, spec, &opts);
         let struct_name = request_structs[0].name.clone();
-        let response = operation.ret.to_rust_type();
-        let method = syn::Ident::new(&operation.method, proc_macro2::Span::call_site());
         let struct_names = request_structs.iter().map(|s| s.name.to_string()).collect::<Vec<_>>();
         let request_structs = request_structs.into_iter().map(|s| s.to_rust_code()).collect::<Vec<_>>();
-        let url = build_url(&operation);
         modules.push(fname.clone());
         let mut import = Import::new(&fname, struct_names);
         import.vis = Visibility::Public;
         imports.push(import);
-        let builder_methods = build_request_struct_builder_methods(&operation);
+        let builder_methods = build_request_struct_builder_methods(&operation, &opts.config);
         let builder_methods = builder_methods
             .into_iter()
             .map(|s| codegen_function(s, quote! { mut self , }));
 
-
-        let assign_inputs = assign_inputs_to_request(&operation.parameters);
+        let send = request::impl_send_request(operation, spec, opts);
 
         let file = quote! {
             use crate::#client_name;
@@ -411,21 +516,7 @@ fn write_request_module(spec: &HirSpec, opts: &PackageConfig) -> Result<()> {
                 #(#builder_methods)*
             }
 
-            impl<'a> ::std::future::IntoFuture for FluentRequest<'a, #struct_name> {
-                type Output = httpclient::InMemoryResult<#response>;
-                type IntoFuture = ::futures::future::BoxFuture<'a, Self::Output>;
-
-                fn into_future(self) -> Self::IntoFuture {
-                    Box::pin(async move {
-                        let url = #url;
-                        let mut r = self.client.client.#method(url);
-                        #assign_inputs
-                        #authenticate
-                        let res = r.await?;
-                        res.json().map_err(Into::into)
-                    })
-                }
-            }
+            #send
         };
         let template = "\
 use serde_json::json;
@@ -444,6 +535,27 @@ use httpclient::InMemoryResponseExt;";
     Ok(())
 }
 
+fn write_error_module(spec: &HirSpec, opts: &PackageConfig) -> Result<()> {
+    let src_path = opts.dest.join("src");
+    io::write_rust_code_to_path(&src_path.join("error.rs"), error::generate_error_rs(spec, &opts.config))
+}
+
+fn write_compression_module_if_needed(opts: &PackageConfig) -> Result<()> {
+    if !opts.config.gzip_requests {
+        return Ok(());
+    }
+    let src_path = opts.dest.join("src");
+    io::write_rust_code_to_path(&src_path.join("compression.rs"), compression::generate_compression_rs())
+}
+
+fn write_webhook_module(spec: &HirSpec, opts: &PackageConfig) -> Result<()> {
+    if spec.webhooks.is_empty() {
+        return Ok(());
+    }
+    let src_path = opts.dest.join("src");
+    let code = webhook::generate_webhook_rs(spec);
+    io::write_rust_code_to_path(&src_path.join("webhook.rs"), code)
+}
 
 fn write_examples(spec: &HirSpec, opts: &PackageConfig) -> Result<()> {
     let example_path = opts.dest.join("examples");
@@ -476,12 +588,26 @@ fn write_serde_module_if_needed(extras: &Extras, dest: &Path) -> Result<()> {
         .then(serde::option_i64_str_module)
         .unwrap_or_default();
 
+    let comma_separated = extras.comma_separated
+        .then(serde::comma_separated_module)
+        .unwrap_or_default();
+
     let code = quote! {
         pub use ::serde::*;
         #null_as_zero
         #date_as_int
         #int_as_str
+        #comma_separated
     };
     let code = format_code(code).unwrap();
     fs::write_file(&src_path, &code)
 }
+
+fn write_validated_module_if_needed(extras: &Extras, dest: &Path) -> Result<()> {
+    if !extras.needs_validated() {
+        return Ok(());
+    }
+    let src_path = dest.join("src").join("validated.rs");
+    let code = validated::generate_validated_rs(&extras.validated_formats, &extras.multiples, &extras.bounded_maps);
+    io::write_rust_code_to_path(&src_path, code)
+}
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::process::Output;
 use cargo_toml::{Inheritable, Manifest, Package, Dependency, DependencyDetail, DepsSet};
+use hir::StringFormat;
 use ln_core::{fs, get_template_file, OutputConfig, PackageConfig};
 use crate::rust::Extras;
 
@@ -46,7 +47,9 @@ pub fn update_cargo_toml(extras: &Extras, opts: &OutputConfig, context: &HashMap
 
     ensure_dependency(&mut m.dependencies, "httpclient", "0.20.2", &[]);
     ensure_dependency(&mut m.dependencies, "serde", "1.0.137", &["derive"]);
-    ensure_dependency(&mut m.dependencies, "serde_json", "1.0.81", &[]);
+    // `Ty::Any` is `serde_json::Value`, whose objects are ordered maps under `preserve_order`.
+    let serde_json_features: &[&str] = if opts.config.preserve_order { &["preserve_order"] } else { &[] };
+    ensure_dependency(&mut m.dependencies, "serde_json", "1.0.81", serde_json_features);
     ensure_dependency(&mut m.dependencies, "futures", "0.3.25", &[]);
     ensure_dependency(&mut m.dependencies, "chrono", "0.4.26", &["serde"]);
     ensure_dependency(&mut m.dev_dependencies, "tokio", "1.18.2", &["full"]);
@@ -73,6 +76,30 @@ pub fn update_cargo_toml(extras: &Extras, opts: &OutputConfig, context: &HashMap
         let d = m.dependencies.get_mut("fake").unwrap();
         d.detail_mut().optional = true;
     }
+    if opts.config.jsonschema {
+        ensure_dependency(&mut m.dependencies, "schemars", "0.8", &["chrono", "rust_decimal"]);
+        let d = m.dependencies.get_mut("schemars").unwrap();
+        d.detail_mut().optional = true;
+        m.features.entry("jsonschema".to_string()).or_insert_with(|| vec!["dep:schemars".to_string()]);
+    }
+    if opts.config.gzip_requests {
+        ensure_dependency(&mut m.dependencies, "flate2", "1.0.28", &[]);
+        let d = m.dependencies.get_mut("flate2").unwrap();
+        d.detail_mut().optional = true;
+        m.features.entry("gzip".to_string()).or_insert_with(|| vec!["dep:flate2".to_string()]);
+    }
+    if extras.validated_formats.contains(&StringFormat::Regex) {
+        ensure_dependency(&mut m.dependencies, "regex", "1.10", &[]);
+    }
+    if opts.config.cancellation {
+        ensure_dependency(&mut m.dependencies, "tokio-util", "0.7", &[]);
+        let d = m.dependencies.get_mut("tokio-util").unwrap();
+        d.detail_mut().optional = true;
+        m.features.entry("cancellation".to_string()).or_insert_with(|| vec!["dep:tokio-util".to_string()]);
+    }
+    if opts.config.thiserror {
+        ensure_dependency(&mut m.dependencies, "thiserror", "1.0", &[]);
+    }
     if extras.basic_auth {
         ensure_dependency(&mut m.dependencies, "base64", "0.21.0", &[]);
     }
@@ -165,4 +192,77 @@ mod tests {
     fn test_default_manifest() {
         let manifest = default_manifest();
     }
+
+    #[test]
+    fn test_crate_name_override() {
+        let spec: openapiv3::OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pet Store
+  version: 1.0.0
+  x-rust-crate-name: petstore-sdk
+  x-rust-crate-version: 2.3.0
+paths: {}
+"#).unwrap();
+        let bare: openapiv3::OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pet Store
+  version: 1.0.0
+paths: {}
+"#).unwrap();
+        // The command line wins, then the spec, then the service name.
+        let (name, version) = crate::crate_name_and_version(&spec, "PetStore", Some("pets".to_string()), Some("0.9.0".to_string()));
+        assert_eq!(name, "pets");
+        assert_eq!(version.as_deref(), Some("0.9.0"));
+        let (name, version) = crate::crate_name_and_version(&bare, "PetStore", None, None);
+        assert_eq!(name, "petstore");
+        assert_eq!(version, None);
+
+        let (package_name, version) = crate::crate_name_and_version(&spec, "PetStore", None, None);
+        let temp = tempfile::tempdir().unwrap();
+        let opts = OutputConfig {
+            dest_path: temp.path().to_path_buf(),
+            build_examples: false,
+            package_name,
+            service_name: "PetStore".to_string(),
+            language: hir::Language::Rust,
+            config: Default::default(),
+            github_repo: None,
+            version,
+        };
+        let extras = crate::rust::calculate_extras(&hir::HirSpec::default());
+        let version = update_cargo_toml(&extras, &opts, &HashMap::new()).unwrap();
+        assert_eq!(version, "2.3.0");
+        let manifest = Manifest::from_path(temp.path().join("Cargo.toml")).unwrap();
+        let package = manifest.package.unwrap();
+        assert_eq!(package.name, "petstore-sdk");
+    }
+
+    #[test]
+    fn test_preserve_order_feature() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut opts = OutputConfig {
+            dest_path: temp.path().to_path_buf(),
+            build_examples: false,
+            package_name: "petstore".to_string(),
+            service_name: "PetStore".to_string(),
+            language: hir::Language::Rust,
+            config: Default::default(),
+            github_repo: None,
+            version: None,
+        };
+        let extras = crate::rust::calculate_extras(&hir::HirSpec::default());
+        update_cargo_toml(&extras, &opts, &HashMap::new()).unwrap();
+        let manifest = Manifest::from_path(temp.path().join("Cargo.toml")).unwrap();
+        assert!(manifest.dependencies["serde_json"].req_features().is_empty());
+
+        // Regenerating on top of the existing manifest adds the feature.
+        opts.config.preserve_order = true;
+        update_cargo_toml(&extras, &opts, &HashMap::new()).unwrap();
+        let manifest = Manifest::from_path(temp.path().join("Cargo.toml")).unwrap();
+        let serde_json = &manifest.dependencies["serde_json"];
+        assert_eq!(serde_json.req(), "1.0.81");
+        assert_eq!(serde_json.req_features(), ["preserve_order"]);
+    }
 }
\ No newline at end of file
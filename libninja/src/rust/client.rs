@@ -1,16 +1,19 @@
+use std::collections::BTreeMap;
+
 use convert_case::{Case, Casing};
 use openapiv3::OpenAPI;
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
 
-use hir::{AuthLocation, AuthStrategy, DocFormat, Location, Parameter, ServerStrategy, Doc, HirSpec, Language, Operation, qualified_env_var};
+use hir::{AuthLocation, AuthStrategy, DocFormat, Location, Parameter, ServerStrategy, Doc, HirSpec, Language, Operation, Ty, qualified_env_var};
 use mir::{ArgIdent, Function, Ident};
 use mir::{Class, Field, FnArg, Visibility};
-use ln_core::PackageConfig;
+use ln_core::{ConfigFlags, PackageConfig};
 
-use crate::rust::codegen::ToRustCode;
+use crate::rust::codegen::{method_name, ToRustCode};
 use crate::rust::codegen::ToRustIdent;
 use crate::rust::codegen::ToRustType;
+use crate::rust::request::{params_struct_params, response_type};
 
 
 pub fn server_url(spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
@@ -29,20 +32,309 @@ pub fn server_url(spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
     }
 }
 
+/// A `ClientConfig` with a `Default` impl, so callers can override one setting and spread the rest.
+/// The shared http client is built from the default config, and `http_client` builds one from any
+/// other, to pass to `init_http_client` or `new_with`.
+pub fn client_config(spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
+    let url = server_url(spec, opt);
+    quote! {
+        #[derive(Debug, Clone)]
+        pub struct ClientConfig {
+            pub base_url: String,
+            pub timeout: std::time::Duration,
+            pub max_retries: u32,
+        }
+
+        impl Default for ClientConfig {
+            fn default() -> Self {
+                Self {
+                    base_url: #url.to_string(),
+                    timeout: std::time::Duration::from_secs(30),
+                    max_retries: 3,
+                }
+            }
+        }
+
+        impl ClientConfig {
+            /// An http client pointed at `base_url`, which gives up on a request after `timeout`
+            /// and retries failed requests up to `max_retries` times.
+            pub fn http_client(&self) -> httpclient::Client {
+                httpclient::Client::new()
+                    .base_url(&self.base_url)
+                    .timeout(self.timeout)
+                    .with_middleware(httpclient::middleware::Retry::new().max_retries(self.max_retries as usize))
+            }
+        }
+    }
+}
+
+/// Extension point for APIs that require signed requests (HMAC, AWS SigV4, ...).
+pub fn request_signer_trait() -> TokenStream {
+    quote! {
+        /// Signs a request once it is fully built, right before it is sent.
+        pub trait RequestSigner: Send + Sync {
+            fn sign<'a>(&self, request: httpclient::RequestBuilder<'a>) -> httpclient::RequestBuilder<'a>;
+        }
+    }
+}
+
+/// The `User-Agent` sent unless the client is given another, from the spec's title and version,
+/// e.g. `Petstore/1.0.0`. Falls back to the package name and version when the spec has no title.
+pub fn user_agent_const(spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
+    let user_agent = if spec.title.is_empty() {
+        opt.user_agent().0
+    } else {
+        format!("{}/{}", spec.title.replace(' ', "-"), spec.version)
+    };
+    quote! {
+        pub const USER_AGENT: &str = #user_agent;
+    }
+}
+
+/// How array query parameters are written, unless the spec fixes it for a parameter.
+pub fn query_array_style_enum() -> TokenStream {
+    quote! {
+        /// How array query parameters are written. Parameters whose `explode` the spec sets ignore this.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+        pub enum QueryArrayStyle {
+            /// `ids[]=1&ids[]=2`
+            #[default]
+            Brackets,
+            /// `ids=1&ids=2`
+            Repeat,
+            /// `ids=1,2`
+            Comma,
+        }
+
+        impl QueryArrayStyle {
+            pub(crate) fn apply<'a>(
+                self,
+                mut r: httpclient::RequestBuilder<'a>,
+                key: &str,
+                values: impl IntoIterator<Item = impl std::fmt::Display>,
+            ) -> httpclient::RequestBuilder<'a> {
+                match self {
+                    QueryArrayStyle::Brackets => {
+                        let key = format!("{}[]", key);
+                        for value in values {
+                            r = r.query(&key, &value.to_string());
+                        }
+                    }
+                    QueryArrayStyle::Repeat => {
+                        for value in values {
+                            r = r.query(key, &value.to_string());
+                        }
+                    }
+                    QueryArrayStyle::Comma => {
+                        let joined = values.into_iter().map(|value| value.to_string()).collect::<Vec<_>>().join(",");
+                        r = r.query(key, &joined);
+                    }
+                }
+                r
+            }
+        }
+    }
+}
+
+/// Initial values for the client's optional settings, shared by every constructor.
+fn client_setting_defaults(opt: &PackageConfig) -> TokenStream {
+    let gzip_threshold = opt.config.gzip_requests.then(|| quote! {
+        gzip_threshold: None,
+    }).unwrap_or_default();
+    let scopes = opt.config.check_scopes.then(|| quote! {
+        scopes: None,
+    }).unwrap_or_default();
+    quote! {
+        accept: None,
+        signer: None,
+        query_array_style: QueryArrayStyle::default(),
+        user_agent: None,
+        #gzip_threshold
+        #scopes
+    }
+}
+
+fn client_setting_fields(opt: &PackageConfig) -> Vec<Field<TokenStream>> {
+    let mut fields = vec![
+        Field {
+            name: "accept".to_string(),
+            ty: quote!(Option<String>),
+            ..Field::default()
+        },
+        Field {
+            name: "signer".to_string(),
+            ty: quote!(Option<Box<dyn RequestSigner>>),
+            ..Field::default()
+        },
+        Field {
+            name: "query_array_style".to_string(),
+            ty: quote!(QueryArrayStyle),
+            ..Field::default()
+        },
+        Field {
+            name: "user_agent".to_string(),
+            ty: quote!(Option<String>),
+            ..Field::default()
+        },
+    ];
+    if opt.config.gzip_requests {
+        fields.push(Field {
+            name: "gzip_threshold".to_string(),
+            ty: quote!(Option<usize>),
+            ..Field::default()
+        });
+    }
+    if opt.config.check_scopes {
+        fields.push(Field {
+            name: "scopes".to_string(),
+            ty: quote!(Option<Vec<String>>),
+            ..Field::default()
+        });
+    }
+    fields
+}
+
+fn client_setting_methods(opt: &PackageConfig) -> Vec<Function<TokenStream>> {
+    let mut methods = vec![
+        Function {
+            name: Ident::new("with_http_client"),
+            public: true,
+            args: vec![FnArg {
+                name: ArgIdent::Ident("client".to_string()),
+                ty: quote!(httpclient::Client),
+                default: None,
+                treatment: None,
+            }],
+            ret: quote!(Self),
+            body: quote! {
+                self.client = Cow::Owned(client);
+                self
+            },
+            ..Function::default()
+        },
+        Function {
+            name: Ident::new("with_accept"),
+            public: true,
+            args: vec![FnArg {
+                name: ArgIdent::Ident("accept".to_string()),
+                ty: quote!(impl Into<String>),
+                default: None,
+                treatment: None,
+            }],
+            ret: quote!(Self),
+            body: quote! {
+                self.accept = Some(accept.into());
+                self
+            },
+            ..Function::default()
+        },
+        Function {
+            name: Ident::new("with_signer"),
+            public: true,
+            args: vec![FnArg {
+                name: ArgIdent::Ident("signer".to_string()),
+                ty: quote!(impl RequestSigner + 'static),
+                default: None,
+                treatment: None,
+            }],
+            ret: quote!(Self),
+            body: quote! {
+                self.signer = Some(Box::new(signer));
+                self
+            },
+            ..Function::default()
+        },
+        Function {
+            name: Ident::new("with_query_array_style"),
+            public: true,
+            args: vec![FnArg {
+                name: ArgIdent::Ident("style".to_string()),
+                ty: quote!(QueryArrayStyle),
+                default: None,
+                treatment: None,
+            }],
+            ret: quote!(Self),
+            body: quote! {
+                self.query_array_style = style;
+                self
+            },
+            ..Function::default()
+        },
+        Function {
+            name: Ident::new("with_user_agent"),
+            doc: Some(Doc("Sent instead of the default `USER_AGENT`.".to_string())),
+            public: true,
+            args: vec![FnArg {
+                name: ArgIdent::Ident("user_agent".to_string()),
+                ty: quote!(impl Into<String>),
+                default: None,
+                treatment: None,
+            }],
+            ret: quote!(Self),
+            body: quote! {
+                self.user_agent = Some(user_agent.into());
+                self
+            },
+            ..Function::default()
+        },
+    ];
+    if opt.config.gzip_requests {
+        methods.push(Function {
+            name: Ident::new("with_gzip_threshold"),
+            public: true,
+            args: vec![FnArg {
+                name: ArgIdent::Ident("bytes".to_string()),
+                ty: quote!(usize),
+                default: None,
+                treatment: None,
+            }],
+            ret: quote!(Self),
+            body: quote! {
+                self.gzip_threshold = Some(bytes);
+                self
+            },
+            ..Function::default()
+        });
+    }
+    if opt.config.check_scopes {
+        methods.push(Function {
+            name: Ident::new("with_scopes"),
+            doc: Some(Doc("The scopes granted to the token. Operations that need other scopes fail without being sent.".to_string())),
+            public: true,
+            args: vec![FnArg {
+                name: ArgIdent::Ident("scopes".to_string()),
+                ty: quote!(impl IntoIterator<Item = impl Into<String>>),
+                default: None,
+                treatment: None,
+            }],
+            ret: quote!(Self),
+            body: quote! {
+                self.scopes = Some(scopes.into_iter().map(Into::into).collect());
+                self
+            },
+            ..Function::default()
+        });
+    }
+    methods
+}
+
 fn build_Client_from_env(spec: &HirSpec, opt: &PackageConfig) -> Function<TokenStream> {
     let auth_struct = opt.authenticator_name().to_rust_struct();
+    let defaults = client_setting_defaults(opt);
     let body = if spec.has_security() {
         let auth_struct = opt.authenticator_name().to_rust_struct();
         quote! {
             Self {
                 client: shared_http_client(),
                 authentication: #auth_struct::from_env(),
+                #defaults
             }
         }
     } else {
         quote! {
             Self {
-                client: shared_http_client()
+                client: shared_http_client(),
+                #defaults
             }
         }
     };
@@ -58,10 +350,12 @@ fn build_Client_from_env(spec: &HirSpec, opt: &PackageConfig) -> Function<TokenS
 
 fn build_Client_with_auth(spec: &HirSpec, opt: &PackageConfig) -> Function<TokenStream> {
     let auth_struct = opt.authenticator_name().to_rust_struct();
+    let defaults = client_setting_defaults(opt);
     let body = quote! {
         Self {
             client: shared_http_client(),
-            authentication
+            authentication,
+            #defaults
         }
     };
     Function {
@@ -81,10 +375,12 @@ fn build_Client_with_auth(spec: &HirSpec, opt: &PackageConfig) -> Function<Token
 
 fn build_Client_new_with(spec: &HirSpec, opt: &PackageConfig) -> Function<TokenStream> {
     let auth_struct = opt.authenticator_name().to_rust_struct();
+    let defaults = client_setting_defaults(opt);
     let body = quote! {
         Self {
             client: Cow::Owned(client),
             authentication,
+            #defaults
         }
     };
     Function {
@@ -124,6 +420,7 @@ pub fn struct_Client(spec: &HirSpec, opt: &PackageConfig) -> Class<TokenStream>
             ..Field::default()
         });
     }
+    instance_fields.extend(client_setting_fields(opt));
 
     let mut class_methods = vec![
         build_Client_from_env(spec, opt)
@@ -131,13 +428,15 @@ pub fn struct_Client(spec: &HirSpec, opt: &PackageConfig) -> Class<TokenStream>
     if spec.has_security() {
         class_methods.push(build_Client_with_auth(spec, opt));
     } else {
+        let defaults = client_setting_defaults(opt);
         class_methods.push(Function {
             name: Ident::new("new"),
             public: true,
             ret: quote!(Self),
             body: quote! {
                 Self {
-                    client: shared_http_client()
+                    client: shared_http_client(),
+                    #defaults
                 }
             },
             ..Function::default()
@@ -148,15 +447,63 @@ pub fn struct_Client(spec: &HirSpec, opt: &PackageConfig) -> Class<TokenStream>
         name: opt.client_name().to_rust_struct(),
         instance_fields,
         class_methods,
+        mut_self_instance_methods: client_setting_methods(opt),
         public: true,
         ..Class::default()
     }
 }
 
-pub fn build_api_client_method(operation: &Operation) -> TokenStream {
+pub fn build_api_client_method(operation: &Operation, config: &ConfigFlags) -> TokenStream {
+    let doc = operation.doc.clone().to_rust_code();
+    let allow = allow_non_snake_case(config);
+    let signature = api_client_method_signature(operation, config);
+    let body = api_client_method_body(operation, config);
+    quote! {
+        #doc
+        #allow
+        pub #signature #body
+    }
+}
+
+/// Fails if a rename isn't a valid method name, or would clash with another operation's method.
+pub fn check_method_names(spec: &HirSpec, config: &ConfigFlags) -> anyhow::Result<()> {
+    // Idents are built without checks later, which would panic on e.g. `get-user`.
+    for (operation, name) in &config.operation_renames {
+        if syn::parse_str::<syn::Ident>(name).is_err() {
+            return Err(anyhow::anyhow!(
+                "Operation `{}` is renamed to `{}`, which is not a valid Rust identifier",
+                operation, name
+            ));
+        }
+    }
+    let mut seen: BTreeMap<String, &Operation> = BTreeMap::new();
+    for operation in &spec.operations {
+        let name = method_name(operation, config).0;
+        if let Some(other) = seen.insert(name.clone(), operation) {
+            if config.operation_rename(operation).is_some() || config.operation_rename(other).is_some() {
+                return Err(anyhow::anyhow!(
+                    "Operations `{}` and `{}` both become the client method `{}` after renaming",
+                    other.name, operation.name, name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Verbatim method names are usually camelCase, which would otherwise warn in the generated crate.
+fn allow_non_snake_case(config: &ConfigFlags) -> TokenStream {
+    if config.verbatim_method_names {
+        quote!(#[allow(non_snake_case)])
+    } else {
+        TokenStream::new()
+    }
+}
+
+fn api_client_method_signature(operation: &Operation, config: &ConfigFlags) -> TokenStream {
     let use_struct = operation.use_required_struct(Language::Rust);
 
-    let fn_args = if use_struct {
+    let mut fn_args = if use_struct {
         let arg_struct = operation.required_struct_name().to_rust_struct();
         vec![quote!(args: request::#arg_struct)]
     } else {
@@ -166,19 +513,48 @@ pub fn build_api_client_method(operation: &Operation) -> TokenStream {
             .filter(|param| !param.optional)
             .map(|param| {
                 let k = param.name.to_rust_ident();
-                let arg_type = param.ty.to_reference_type(TokenStream::new());
+                let arg_type = match slice_arg_item(param) {
+                    Some(item) => quote!(impl AsRef<[#item]>),
+                    None => param.ty.to_reference_type(TokenStream::new()),
+                };
                 quote!(#k: #arg_type)
             })
             .collect()
     };
+    if !params_struct_params(operation, config).is_empty() {
+        let params_struct = operation.params_struct_name().to_rust_struct();
+        fn_args.push(quote!(params: request::#params_struct));
+    }
+    let request_struct = operation.request_struct_name().to_rust_struct();
+    let name = method_name(operation, config);
+    quote! {
+        fn #name(&self, #(#fn_args),*) -> FluentRequest<'_, request::#request_struct>
+    }
+}
+
+/// The item type of a required array of owned items. Such an argument takes `impl AsRef<[T]>`,
+/// so callers can pass either a slice or a `Vec`.
+fn slice_arg_item(param: &Parameter) -> Option<TokenStream> {
+    match &param.ty {
+        Ty::Array(inner) if !inner.is_reference_type() => Some(inner.to_rust_type()),
+        _ => None,
+    }
+}
 
+fn api_client_method_body(operation: &Operation, config: &ConfigFlags) -> TokenStream {
+    let use_struct = operation.use_required_struct(Language::Rust);
+    let in_params_struct = params_struct_params(operation, config);
     let struct_field_values: Vec<TokenStream> = operation
         .parameters
         .iter()
         .map(|param| {
             let name = param.name.to_rust_ident();
-            if param.optional {
+            if in_params_struct.iter().any(|p| p.name == param.name) {
+                quote!(#name: params.#name)
+            } else if param.optional {
                 quote!(#name: None)
+            } else if !use_struct && slice_arg_item(param).is_some() {
+                quote!(#name: #name.as_ref().to_vec())
             } else if param.ty.is_reference_type() {
                 let iterable = param.ty.is_iterable();
                 let mut value = if iterable {
@@ -198,12 +574,9 @@ pub fn build_api_client_method(operation: &Operation) -> TokenStream {
         })
         .collect();
 
-    let doc = operation.doc.clone().to_rust_code();
     let request_struct = operation.request_struct_name().to_rust_struct();
-    let name = &operation.name.to_rust_ident();
     quote! {
-        #doc
-        pub fn #name(&self, #(#fn_args),*) -> FluentRequest<'_, request::#request_struct> {
+        {
             FluentRequest {
                 client: self,
                 params: request::#request_struct {
@@ -214,14 +587,120 @@ pub fn build_api_client_method(operation: &Operation) -> TokenStream {
     }
 }
 
-pub fn impl_ServiceClient_paths(spec: &HirSpec) -> Vec<TokenStream> {
+pub fn impl_ServiceClient_paths(spec: &HirSpec, opt: &PackageConfig) -> Vec<TokenStream> {
     let mut result = vec![];
     for operation in &spec.operations {
-        result.push(build_api_client_method(operation));
+        result.push(build_api_client_method(operation, &opt.config));
     }
     result
 }
 
+pub fn tag_trait_name(tag: &str) -> Ident {
+    format!("{} Api", tag).to_rust_struct()
+}
+
+/// The signature for an operation on a trait meant to be implemented by more than the client. Instead of
+/// returning a `FluentRequest` tied to the concrete client, it takes the whole request struct and
+/// returns a boxed future, so other implementations (e.g. mocks) don't need a real client.
+fn boxed_method_signature(operation: &Operation, config: &ConfigFlags) -> TokenStream {
+    let name = method_name(operation, config);
+    let request_struct = operation.request_struct_name().to_rust_struct();
+    let response = response_type(operation);
+    quote! {
+        fn #name(&self, params: request::#request_struct) -> ::futures::future::BoxFuture<'_, Result<#response, crate::ClientError>>
+    }
+}
+
+/// The client's implementation of a `boxed_method_signature` method: it just sends the request.
+fn boxed_method_body() -> TokenStream {
+    quote! {
+        {
+            ::std::future::IntoFuture::into_future(FluentRequest { client: self, params })
+        }
+    }
+}
+
+/// With `tag_traits`, each tag's operations are also declared on a trait that the client implements.
+/// Operations with several tags go on the trait for the first one. The client keeps its inherent
+/// fluent methods, so callers only go through the trait when they want to swap the client out.
+pub fn tag_traits(spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
+    if !opt.config.tag_traits {
+        return TokenStream::new();
+    }
+    let mut by_tag: BTreeMap<&str, Vec<&Operation>> = BTreeMap::new();
+    for operation in &spec.operations {
+        if let Some(tag) = operation.tags.first() {
+            by_tag.entry(tag).or_default().push(operation);
+        }
+    }
+    let client_struct_name = opt.client_name().to_rust_struct();
+    let traits = by_tag.into_iter().map(|(tag, operations)| {
+        let trait_name = tag_trait_name(tag);
+        let declarations = operations.iter().map(|operation| {
+            let doc = operation.doc.clone().to_rust_code();
+            let allow = allow_non_snake_case(&opt.config);
+            let signature = boxed_method_signature(operation, &opt.config);
+            quote! {
+                #doc
+                #allow
+                #signature;
+            }
+        });
+        let implementations = operations.iter().map(|operation| {
+            let signature = boxed_method_signature(operation, &opt.config);
+            let body = boxed_method_body();
+            quote! {
+                #signature #body
+            }
+        });
+        quote! {
+            pub trait #trait_name {
+                #(#declarations)*
+            }
+            impl #trait_name for #client_struct_name {
+                #(#implementations)*
+            }
+        }
+    });
+    quote! {
+        #(#traits)*
+    }
+}
+
+/// With `dyn_client`, a trait over every operation that can be used as a trait object, with the
+/// same boxed-future methods as the `tag_traits` traits.
+pub fn dyn_client(spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
+    if !opt.config.dyn_client {
+        return TokenStream::new();
+    }
+    let client_struct_name = opt.client_name().to_rust_struct();
+    let declarations = spec.operations.iter().map(|operation| {
+        let doc = operation.doc.clone().to_rust_code();
+        let allow = allow_non_snake_case(&opt.config);
+        let signature = boxed_method_signature(operation, &opt.config);
+        quote! {
+            #doc
+            #allow
+            #signature;
+        }
+    });
+    let implementations = spec.operations.iter().map(|operation| {
+        let signature = boxed_method_signature(operation, &opt.config);
+        let body = boxed_method_body();
+        quote! {
+            #signature #body
+        }
+    });
+    quote! {
+        pub trait DynClient {
+            #(#declarations)*
+        }
+        impl DynClient for #client_struct_name {
+            #(#implementations)*
+        }
+    }
+}
+
 pub fn authenticate_variant(
     req: &AuthStrategy,
     opt: &PackageConfig,
@@ -302,7 +781,7 @@ pub fn build_Client_authenticate(spec: &HirSpec, opt: &PackageConfig) -> TokenSt
 
 pub fn impl_Client(spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
     let client_struct_name = opt.client_name().to_rust_struct();
-    let path_fns = impl_ServiceClient_paths(spec);
+    let path_fns = impl_ServiceClient_paths(spec, opt);
 
     let security = spec.has_security();
     let authenticate = security.then(|| {
@@ -434,3 +913,149 @@ pub fn impl_Authentication(spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::rust::format::format_code;
+    use crate::rust::test_util::{param, petstore_opt};
+
+    use super::*;
+
+    #[test]
+    fn test_method_name_casing() {
+        let operation = Operation {
+            name: "listPets".to_string(),
+            ..Operation::default()
+        };
+        let config = ConfigFlags::default();
+        let code = format_code(build_api_client_method(&operation, &config)).unwrap();
+        assert!(code.contains("pub fn list_pets(&self) -> FluentRequest<'_, request::ListPetsRequest>"));
+
+        let config = ConfigFlags { verbatim_method_names: true, ..ConfigFlags::default() };
+        let code = format_code(build_api_client_method(&operation, &config)).unwrap();
+        assert!(code.contains("#[allow(non_snake_case)]"));
+        assert!(code.contains("pub fn listPets(&self) -> FluentRequest<'_, request::ListPetsRequest>"));
+    }
+
+    #[test]
+    fn test_operation_rename() {
+        let operation = Operation {
+            name: "get_v1_users_user_id".to_string(),
+            method: "get".to_string(),
+            path: "/v1/users/{user_id}".to_string(),
+            ..Operation::default()
+        };
+        let mut config = ConfigFlags::default();
+        config.operation_renames.insert("get_v1_users_user_id".to_string(), "get_user".to_string());
+        let code = format_code(build_api_client_method(&operation, &config)).unwrap();
+        assert!(code.contains("pub fn get_user(&self)"));
+
+        // Method and path work as a key too, for operations without an `operationId`.
+        let mut config = ConfigFlags::default();
+        config.operation_renames.insert("GET /v1/users/{user_id}".to_string(), "fetch_user".to_string());
+        let code = format_code(build_api_client_method(&operation, &config)).unwrap();
+        assert!(code.contains("pub fn fetch_user(&self)"));
+
+        let spec = HirSpec {
+            operations: vec![operation, Operation {
+                name: "fetchUser".to_string(),
+                ..Operation::default()
+            }],
+            ..HirSpec::default()
+        };
+        let err = check_method_names(&spec, &config).unwrap_err();
+        assert!(err.to_string().contains("`fetch_user`"));
+        assert!(check_method_names(&spec, &ConfigFlags::default()).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_operation_rename() {
+        let spec = HirSpec {
+            operations: vec![Operation {
+                name: "getUser".to_string(),
+                ..Operation::default()
+            }],
+            ..HirSpec::default()
+        };
+        for name in ["get-user", "1user", "type"] {
+            let mut config = ConfigFlags::default();
+            config.operation_renames.insert("getUser".to_string(), name.to_string());
+            let err = check_method_names(&spec, &config).unwrap_err();
+            assert!(err.to_string().contains("not a valid Rust identifier"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_dyn_client() {
+        let spec = HirSpec {
+            operations: vec![Operation {
+                name: "getPet".to_string(),
+                ret: hir::Ty::model("Pet"),
+                ..Operation::default()
+            }],
+            ..HirSpec::default()
+        };
+        let mut opt = petstore_opt(ConfigFlags::default());
+        assert!(dyn_client(&spec, &opt).is_empty());
+
+        opt.config.dyn_client = true;
+        let code = format_code(dyn_client(&spec, &opt)).unwrap();
+        // No generics or `impl Trait`, so `Box<dyn DynClient>` works.
+        assert!(code.contains("pub trait DynClient {"));
+        assert!(code.contains("params: request::GetPetRequest"));
+        assert!(code.contains("::futures::future::BoxFuture<'_, Result<Pet, crate::ClientError>>"));
+        assert!(code.contains("impl DynClient for PetstoreClient {"));
+        assert!(code.contains("::std::future::IntoFuture::into_future(FluentRequest {"));
+        assert!(!code.contains("fn get_pet<"));
+    }
+
+    #[test]
+    fn test_user_agent() {
+        let spec = HirSpec {
+            operations: vec![Operation {
+                name: "listPets".to_string(),
+                method: "get".to_string(),
+                path: "/pets".to_string(),
+                ..Operation::default()
+            }],
+            title: "Pet Store".to_string(),
+            version: "1.0.0".to_string(),
+            ..HirSpec::default()
+        };
+        let opt = petstore_opt(ConfigFlags::default());
+        let code = format_code(user_agent_const(&spec, &opt)).unwrap();
+        assert!(code.contains("pub const USER_AGENT: &str = \"Pet-Store/1.0.0\";"));
+
+        // Every request sends the override if there is one, and the default otherwise.
+        let request = crate::rust::request::impl_send_request(&spec.operations[0], &spec, &opt);
+        let request = format_code(request).unwrap();
+        assert!(request.contains("let agent = self.client.user_agent.as_deref().unwrap_or(crate::USER_AGENT);"));
+        assert!(request.contains("r = r.header(\"User-Agent\", agent);"));
+        let client = format_code(struct_Client(&spec, &opt).to_rust_code()).unwrap();
+        assert!(client.contains("pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {"));
+        assert!(client.contains("user_agent: None,"));
+    }
+
+    #[test]
+    fn test_required_array_slice() {
+        let operation = Operation {
+            name: "listPets".to_string(),
+            method: "get".to_string(),
+            path: "/pets".to_string(),
+            parameters: vec![param("ids", Ty::Array(Box::new(Ty::integer())), Location::Query)],
+            ..Operation::default()
+        };
+        let config = ConfigFlags::default();
+        let code = format_code(build_api_client_method(&operation, &config)).unwrap();
+        assert!(code.contains("ids: impl AsRef<[i64]>"));
+        assert!(code.contains("ids: ids.as_ref().to_vec(),"));
+
+        let spec = HirSpec {
+            operations: vec![operation],
+            ..HirSpec::default()
+        };
+        let opt = petstore_opt(config);
+        let example = crate::rust::codegen::generate_example(&spec.operations[0], &opt, &spec).unwrap();
+        assert!(example.contains("let ids = vec![1];"));
+    }
+}
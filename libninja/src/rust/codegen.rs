@@ -3,7 +3,6 @@ use convert_case::{Case, Casing};
 use openapiv3::{ArrayType, OpenAPI, Schema, SchemaKind};
 use proc_macro2::{Span, TokenStream, TokenTree};
 use quote::{quote, TokenStreamExt};
-use regex::{Captures, Regex};
 use syn::Path;
 
 use mir::{ArgIdent, Class, Field, File, Function, Ident, Import, ImportItem, Literal, Visibility};
@@ -11,7 +10,8 @@ pub use typ::*;
 pub use example::*;
 pub use ident::*;
 use ln_core::extractor::is_primitive;
-use hir::{HirSpec, NewType, Parameter, ParamKey, Record, StrEnum, Struct, Ty, Doc, HirField};
+use ln_core::ConfigFlags;
+use hir::{HirSpec, NewType, Operation, Parameter, ParamKey, Record, StrEnum, StringFormat, Struct, Ty, Doc, HirField};
 use crate::rust::format;
 
 mod example;
@@ -51,6 +51,18 @@ pub fn sanitize_ident(s: &str) -> Ident {
     Ident(sanitize(s))
 }
 
+/// The client method for an operation. Snake case, unless `verbatim_method_names` is set.
+/// An entry in `operation_renames` replaces the derived name entirely.
+pub fn method_name(operation: &Operation, config: &ConfigFlags) -> Ident {
+    if let Some(name) = config.operation_rename(operation) {
+        Ident(name.to_string())
+    } else if config.verbatim_method_names {
+        Ident(sanitize_verbatim(&operation.name))
+    } else {
+        sanitize_ident(&operation.name)
+    }
+}
+
 /// Use this for codegen structs: Function, Class, etc.
 pub trait ToRustCode {
     fn to_rust_code(self) -> TokenStream;
@@ -136,6 +148,23 @@ pub fn codegen_function(func: Function<TokenStream>, self_arg: TokenStream) -> T
 }
 
 
+/// Whether `tokens` mention the lifetime `'name`.
+fn uses_lifetime(tokens: TokenStream, name: &str) -> bool {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(group) if uses_lifetime(group.stream(), name) => return true,
+            TokenTree::Punct(p) if p.as_char() == '\'' => {
+                if matches!(tokens.peek(), Some(TokenTree::Ident(ident)) if ident == name) {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
 impl ToRustCode for Class<TokenStream> {
     fn to_rust_code(self) -> TokenStream {
         let is_pub = pub_tok(self.public);
@@ -156,15 +185,34 @@ impl ToRustCode for Class<TokenStream> {
         });
 
         let doc = self.doc.to_rust_code();
-        let lifetimes = if self.lifetimes.is_empty() {
+        // A lifetime no field borrows with is an error (E0392), so it's dropped.
+        let used_lifetimes = self.lifetimes.iter()
+            .filter(|l| self.instance_fields.iter().any(|f| uses_lifetime(f.ty.clone(), l.trim_start_matches('\''))))
+            .collect::<Vec<_>>();
+        let lifetimes = if used_lifetimes.is_empty() && self.generics.is_empty() {
             quote! {}
         } else {
-            let lifetimes = self.lifetimes.iter().map(|l| {
+            let lifetimes = used_lifetimes.iter().map(|l| {
                 let name = syn::Lifetime::new(l, Span::call_site());
                 quote! { # name }
             });
-            quote! { < # ( # lifetimes), * > }
+            let generics = self.generics.iter().map(|g| {
+                let name = syn::Ident::new(g, Span::call_site());
+                quote! { # name }
+            });
+            let params = lifetimes.chain(generics);
+            quote! { < # ( # params), * > }
         };
+        // serde's derived bounds don't always hold for a type parameter (e.g. behind `Option` or a
+        // `#[serde(with)]`), so they're spelled out.
+        let derives_serde = self.decorators.iter().any(|d| d.to_string().contains("Deserialize"));
+        let serde_bound = (derives_serde && !self.generics.is_empty()).then(|| {
+            let serialize = self.generics.iter().map(|g| format!("{}: serde::Serialize", g)).collect::<Vec<_>>().join(", ");
+            let deserialize = self.generics.iter().map(|g| format!("{}: serde::de::DeserializeOwned", g)).collect::<Vec<_>>().join(", ");
+            quote! {
+                #[serde(bound(serialize = #serialize, deserialize = #deserialize))]
+            }
+        });
         let decorator = self.decorators;
         let name = self.name;
         quote! {
@@ -172,6 +220,7 @@ impl ToRustCode for Class<TokenStream> {
             #(
                 #decorator
             )*
+            #serde_bound
             #is_pub struct #name #lifetimes {
                 #(#fields,)*
             }
@@ -248,7 +297,6 @@ impl ToRustCode for Import {
             }
         }
         let feature = std::mem::take(&mut self.feature).map(|f| {
-            let f = syn::Ident::new(&f, Span::call_site());
             quote!(#[cfg(feature = #f)])
         }).unwrap_or_default();
         let import = inner(self);
@@ -294,7 +342,24 @@ impl ToRustCode for Option<Doc> {
     }
 }
 
-pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value: bool) -> Result<TokenStream> {
+/// Whether an example value is sent in a request or received in a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExampleContext {
+    Request,
+    Response,
+}
+
+impl ExampleContext {
+    /// `readOnly` fields have no place in requests, nor `writeOnly` fields in responses.
+    fn skips(&self, field: &HirField) -> bool {
+        match self {
+            ExampleContext::Request => field.read_only,
+            ExampleContext::Response => field.write_only,
+        }
+    }
+}
+
+pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, config: &ConfigFlags, use_ref_value: bool, context: ExampleContext) -> Result<TokenStream> {
     let s = match ty {
         Ty::String => {
             let s = format!("your {}", name.to_case(Case::Lower));
@@ -304,7 +369,8 @@ pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value:
                 quote!(#s.to_owned())
             }
         }
-        Ty::Integer { .. } => quote!(1),
+        Ty::Char => quote!('a'),
+        Ty::Integer { .. } | Ty::Unsigned { .. } => quote!(1),
         Ty::Float => quote!(1.0),
         Ty::Boolean => quote!(true),
         Ty::Array(inner) => {
@@ -313,46 +379,86 @@ pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value:
             } else {
                 use_ref_value
             };
-            let inner = to_rust_example_value(inner, name, spec, use_ref_value)?;
+            let inner = to_rust_example_value(inner, name, spec, config, use_ref_value, context)?;
             if use_ref_value {
                 quote!(&[#inner])
             } else {
                 quote!(vec![#inner])
             }
         }
+        Ty::Option(inner) => {
+            let inner = to_rust_example_value(inner, name, spec, config, false, context)?;
+            quote!(Some(#inner))
+        }
+        Ty::Tuple(items) => {
+            let items = items.iter()
+                .map(|ty| to_rust_example_value(ty, name, spec, config, false, context))
+                .collect::<Result<Vec<_>>>()?;
+            quote!((#(#items,)*))
+        }
         Ty::Model(model) => {
             let record = spec.get_record(model)?;
             let force_ref = model.ends_with("Required");
             match record {
-                Record::Struct(Struct { name: _name, fields, nullable, docs: _docs }) => {
+                Record::Struct(Struct { name: _name, fields, nullable, docs: _docs, .. }) => {
                     let fields = fields.iter().map(|(name, field)| {
                         let not_ref = !force_ref || field.optional;
-                        let mut value = to_rust_example_value(&field.ty, name, spec, !not_ref)?;
-                        let name = name.to_rust_ident();
+                        let name_ident = name.to_rust_ident();
+                        if context.skips(field) {
+                            let value = if field.optional { quote!(None) } else { quote!(Default::default()) };
+                            return Ok(quote!(#name_ident: #value));
+                        }
+                        let mut value = to_rust_example_value(&field.ty, name, spec, config, !not_ref, context)?;
                         if field.optional {
                             value = quote!(Some(#value));
                         }
-                        Ok(quote!(#name: #value))
+                        Ok(quote!(#name_ident: #value))
                     }).collect::<Result<Vec<_>, anyhow::Error>>()?;
                     let model = model.to_rust_struct();
                     quote!(#model{#(#fields),*})
                 }
-                Record::NewType(NewType { name, fields, docs: _docs }) => {
+                Record::NewType(NewType { name, fields, .. }) => {
                     let fields = fields.iter().map(|f| {
-                        to_rust_example_value(&f.ty, name, spec, false)
+                        to_rust_example_value(&f.ty, name, spec, config, false, context)
                     }).collect::<Result<Vec<_>, _>>()?;
                     let name = name.to_rust_struct();
                     quote!(#name(#(#fields),*))
                 }
-                Record::Enum(StrEnum { name, variants, docs: _docs }) => {
-                    let variant = variants.first().unwrap();
-                    let variant = variant.to_rust_struct();
+                Record::Enum(e) => {
+                    let model = model.to_rust_struct();
+                    if config.single_variant_marker && e.single_variant().is_some() {
+                        quote!(#model)
+                    } else {
+                        let variant = e.variants.first().unwrap();
+                        let variant = variant.to_rust_struct();
+                        quote!(#model::#variant)
+                    }
+                }
+                Record::Union(u) => {
                     let model = model.to_rust_struct();
-                    quote!(#model::#variant)
+                    let variant = u.variants.first().ok_or_else(|| anyhow::anyhow!("Union {} has no variants", u.name))?;
+                    let name = variant.name.to_rust_struct();
+                    if variant.is_struct() {
+                        let fields = variant.fields.iter().map(|(name, field)| {
+                            let mut value = to_rust_example_value(&field.ty, name, spec, config, false, context)?;
+                            if field.optional {
+                                value = quote!(Some(#value));
+                            }
+                            let name = name.to_rust_ident();
+                            Ok(quote!(#name: #value))
+                        }).collect::<Result<Vec<_>>>()?;
+                        quote!(#model::#name { #(#fields),* })
+                    } else {
+                        let mut value = to_rust_example_value(&variant.field.ty, &variant.name, spec, config, false, context)?;
+                        if variant.field.optional {
+                            value = quote!(Some(#value));
+                        }
+                        quote!(#model::#name(#value))
+                    }
                 }
                 Record::TypeAlias(name, HirField { ty, optional, .. }) => {
                     let not_ref = !force_ref || !optional;
-                    let ty = to_rust_example_value(ty, name, spec, not_ref)?;
+                    let ty = to_rust_example_value(ty, name, spec, config, not_ref, context)?;
                     if *optional {
                         quote!(Some(#ty))
                     } else {
@@ -365,11 +471,45 @@ pub fn to_rust_example_value(ty: &Ty, name: &str, spec: &HirSpec, use_ref_value:
         Ty::Any => quote!(serde_json::json!({})),
         Ty::Date { .. } => quote!(chrono::Utc::now().date_naive()),
         Ty::DateTime { .. } => quote!(chrono::Utc::now()),
-        Ty::Currency { .. } => quote!(rust_decimal_macros::dec!(100.01))
+        Ty::Currency { .. } => quote!(rust_decimal_macros::dec!(100.01)),
+        Ty::Format(format) => {
+            let s = example_for_format(*format);
+            quote!(#s.parse().unwrap())
+        }
+        Ty::MultipleOf(multiple) => {
+            let value = proc_macro2::Literal::i64_suffixed(*multiple as i64);
+            quote!(#value.try_into().unwrap())
+        }
+        Ty::BoundedMap(count) => {
+            let name = crate::rust::validated::bounded_map_type_name(*count);
+            let len = proc_macro2::Literal::u64_unsuffixed(count.min.unwrap_or_default());
+            quote! {
+                crate::validated::#name::new((0..#len).map(|i| (i.to_string(), serde_json::Value::Null)).collect()).unwrap()
+            }
+        }
     };
     Ok(s)
 }
 
+/// A value that parses into the type for the format.
+fn example_for_format(format: StringFormat) -> &'static str {
+    match format {
+        StringFormat::Ipv4 => "127.0.0.1",
+        StringFormat::Ipv6 => "::1",
+        StringFormat::Hostname => "example.com",
+        StringFormat::Email => "user@example.com",
+        StringFormat::Uuid => "00000000-0000-0000-0000-000000000000",
+        StringFormat::Uri => "https://example.com",
+        StringFormat::JsonPointer => "/foo/0",
+        StringFormat::RelativeJsonPointer => "0/foo",
+        StringFormat::Password => "***",
+        StringFormat::Regex => ".*",
+        StringFormat::Color => "#ffffff",
+        StringFormat::Ulid => "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+        StringFormat::Cuid => "cjld2cjxh0000qzrmn831i7rn",
+    }
+}
+
 impl ToRustCode for Literal<String> {
     fn to_rust_code(self) -> TokenStream {
         let s = self.0;
@@ -406,72 +546,53 @@ pub fn is_referenceable(schema: &Schema, spec: &OpenAPI) -> bool {
     }
 }
 
-fn rewrite_names(s: &str) -> String {
-    // custom logic for Github openapi spec lol
-    if s == "+1" {
-        return "PlusOne".to_string();
-    } else if s == "-1" {
-        return "MinusOne".to_string();
-    }
-    s.replace('/', "_")
-        .replace(['@', '\'', '+'], "")
-        .replace(':', " ")
-        .replace('.', "_")
-}
+#[cfg(test)]
+mod tests {
+    use hir::{HirField, HirSpec, Record, StringFormat, Struct, Ty};
+    use ln_core::ConfigFlags;
+    use mir::{Class, Field, Ident, import, Import, Visibility};
+    use quote::quote;
 
-fn sanitize(s: impl AsRef<str>) -> String {
-    let s = s.as_ref();
-    let original = s;
-    let s = rewrite_names(s);
-    let regex = Regex::new("[a-z]_[0-9]").unwrap();
-    let mut s = s.to_case(Case::Snake);
-    s = regex
-        .replace_all(&s, |c: &Captures| {
-            let mut c = c.get(0).unwrap().as_str().to_string();
-            c.remove(1);
-            c
-        })
-        .into();
-    if is_restricted(&s) {
-        s += "_"
-    }
-    if s.chars().next().unwrap().is_numeric() {
-        s = format!("_{}", s)
-    }
-
-    let mut s = s.replace('[', "_lsq_")
-        .replace(']', "_rsq_");
-
-    assert_valid_ident(&s, original);
-    s
-}
+    use crate::rust::codegen::{to_rust_example_value, ExampleContext, ToRustCode, ToRustIdent, ToRustType};
+    use crate::rust::format::format_code;
 
-fn sanitize_struct(s: impl AsRef<str>) -> Ident {
-    let s = s.as_ref();
-    let original = s;
-    let s = rewrite_names(s);
-    let mut s = s.to_case(Case::Pascal);
-    if is_restricted(&s) {
-        s += "Struct"
+    #[test]
+    fn test_password_example() {
+        let ty = Ty::Format(StringFormat::Password);
+        let example = to_rust_example_value(&ty, "password", &HirSpec::default(), &ConfigFlags::default(), false, ExampleContext::Request).unwrap();
+        assert_eq!(example.to_string(), "\"***\" . parse () . unwrap ()");
     }
-    assert_valid_ident(&s, &original);
-    Ident(s)
-}
 
-fn assert_valid_ident(s: &str, original: &str) {
-    if s.chars().next().map(|c| c.is_numeric()).unwrap_or_default() {
-        panic!("Numeric identifier: {}", original)
-    }
-    if s.contains('.') {
-        panic!("Dot in identifier: {}", original)
+    #[test]
+    fn test_nullable_items_example() {
+        let ty = Ty::Array(Box::new(Ty::Option(Box::new(Ty::integer()))));
+        assert_eq!(ty.to_rust_type().to_string(), "Vec < Option < i64 > >");
+        let example = to_rust_example_value(&ty, "scores", &HirSpec::default(), &ConfigFlags::default(), false, ExampleContext::Request).unwrap();
+        assert_eq!(example.to_string(), "vec ! [Some (1)]");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use mir::{Ident, import, Import};
-
-    use crate::rust::codegen::{ToRustCode, ToRustIdent};
+    #[test]
+    fn test_write_only_absent_from_response_example() {
+        let spec = HirSpec {
+            schemas: [("Account".to_string(), Record::Struct(Struct {
+                name: "Account".to_string(),
+                fields: [
+                    ("id".to_string(), HirField { ty: Ty::String, read_only: true, ..HirField::default() }),
+                    ("password".to_string(), HirField { ty: Ty::String, write_only: true, optional: true, ..HirField::default() }),
+                ].into_iter().collect(),
+                ..Struct::default()
+            }))].into_iter().collect(),
+            ..HirSpec::default()
+        };
+        let ty = Ty::model("Account");
+        let config = ConfigFlags::default();
+        let response = to_rust_example_value(&ty, "account", &spec, &config, false, ExampleContext::Response).unwrap().to_string();
+        assert!(response.contains("password : None"));
+        assert!(response.contains("id : \"your id\" . to_owned ()"));
+        let request = to_rust_example_value(&ty, "account", &spec, &config, false, ExampleContext::Request).unwrap().to_string();
+        assert!(request.contains("id : Default :: default ()"));
+        assert!(request.contains("password : Some (\"your password\" . to_owned ())"));
+    }
 
     #[test]
     fn test_to_ident() {
@@ -486,6 +607,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_generic_serde_bound() {
+        let class = Class {
+            name: Ident::new("Page"),
+            instance_fields: vec![Field {
+                name: "items".to_string(),
+                ty: quote!(Vec<T>),
+                visibility: Visibility::Public,
+                ..Field::default()
+            }],
+            generics: vec!["T".to_string()],
+            public: true,
+            decorators: vec![quote!(#[derive(Debug, Clone, Serialize, Deserialize)])],
+            ..Class::default()
+        };
+        let tokens = class.to_rust_code();
+        assert!(tokens.to_string().contains("# [serde (bound (serialize = \"T: serde::Serialize\" , deserialize = \"T: serde::de::DeserializeOwned\"))]"));
+        let code = format_code(tokens).unwrap();
+        assert!(code.contains("pub struct Page<T> {"));
+        assert!(code.contains("impl<T> Page<T> {"));
+    }
+
+    #[test]
+    fn test_unused_lifetime() {
+        let class = |ty: TokenStream| Class {
+            name: Ident::new("ListPetsRequired"),
+            instance_fields: vec![Field {
+                name: "limit".to_string(),
+                ty,
+                visibility: Visibility::Public,
+                ..Field::default()
+            }],
+            lifetimes: vec!["'a".to_string()],
+            public: true,
+            ..Class::default()
+        };
+        // `struct ListPetsRequired<'a> { limit: i64 }` would fail with E0392.
+        let code = format_code(class(quote!(i64)).to_rust_code()).unwrap();
+        assert!(code.contains("pub struct ListPetsRequired {"));
+        assert!(code.contains("impl ListPetsRequired {"));
+        syn::parse_file(&code).unwrap();
+
+        let code = format_code(class(quote!(&'a [i64])).to_rust_code()).unwrap();
+        assert!(code.contains("pub struct ListPetsRequired<'a> {"));
+        assert!(code.contains("impl<'a> ListPetsRequired<'a> {"));
+    }
+
+    #[test]
+    fn test_keyword_field_ident() {
+        // Not every struct derives serde, so the field itself carries no `serde(rename)`.
+        let field = Field {
+            name: "type".to_string(),
+            ty: quote!(String),
+            visibility: Visibility::Public,
+            ..Field::default()
+        };
+        assert_eq!(field.to_rust_code().to_string(), "pub type_ : String ,");
+    }
+
     #[test]
     fn test_star() {
         let i = import!("super::*");
@@ -519,6 +699,15 @@ mod tests {
             "use foo_bar ;"
         );
     }
+
+    #[test]
+    fn test_feature_gated_reexport() {
+        let import = Import::new("export_pets", vec!["ExportPetsRequest"]).public().feature("export");
+        assert_eq!(
+            import.to_rust_code().to_string(),
+            "# [cfg (feature = \"export\")] pub use export_pets :: { ExportPetsRequest } ;"
+        );
+    }
 }
 
 pub fn is_restricted(s: &str) -> bool {
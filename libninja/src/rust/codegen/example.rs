@@ -6,38 +6,53 @@ use ln_macro::rfunction;
 use mir::{File, Import};
 
 use crate::PackageConfig;
-use crate::rust::codegen::{to_rust_example_value, ToRustCode};
+use ln_core::ConfigFlags;
+use crate::rust::codegen::{method_name, to_rust_example_value, ExampleContext, ToRustCode};
 use crate::rust::codegen::ToRustIdent;
+use crate::rust::request::params_struct_params;
 use crate::rust::format::format_code;
 
 pub trait ToRustExample {
-    fn to_rust_example(&self, spec: &HirSpec) -> anyhow::Result<TokenStream>;
+    fn to_rust_example(&self, spec: &HirSpec, config: &ConfigFlags) -> anyhow::Result<TokenStream>;
 }
 
 impl ToRustExample for Parameter {
-    fn to_rust_example(&self, spec: &HirSpec) -> anyhow::Result<TokenStream> {
-        to_rust_example_value(&self.ty, &self.name, spec, false)
+    fn to_rust_example(&self, spec: &HirSpec, config: &ConfigFlags) -> anyhow::Result<TokenStream> {
+        to_rust_example_value(&self.ty, &self.name, spec, config, false, ExampleContext::Request)
     }
 }
 
 
 pub fn generate_example(operation: &Operation, opt: &PackageConfig, spec: &HirSpec) -> anyhow::Result<String> {
     let args = operation.function_args(Language::Rust);
-    let declarations = args.iter().map(|p| {
+    let mut declarations = args.iter().map(|p| {
         let ident = p.name.to_rust_ident();
-        let value = to_rust_example_value(&p.ty, &p.name, spec, true)?;
+        let value = to_rust_example_value(&p.ty, &p.name, spec, &opt.config, true, ExampleContext::Request)?;
         Ok(quote! {
             let #ident = #value;
         })
     }).collect::<anyhow::Result<Vec<_>, anyhow::Error>>()?;
-    let fn_args = args.iter().map(|p| p.name.to_rust_ident());
-    let optionals = operation.optional_args().into_iter().map(|p| {
+    let mut fn_args = args.iter().map(|p| p.name.to_rust_ident()).collect::<Vec<_>>();
+    let in_params_struct = params_struct_params(operation, &opt.config);
+    let (params_struct_optionals, optionals): (Vec<_>, Vec<_>) = operation.optional_args().into_iter()
+        .partition(|p| in_params_struct.iter().any(|s| s.name == p.name));
+    let setters = |params: Vec<&Parameter>| params.into_iter().map(|p| {
         let ident = p.name.to_rust_ident();
-        let value = to_rust_example_value(&p.ty, &p.name, spec, true)?;
+        let value = to_rust_example_value(&p.ty, &p.name, spec, &opt.config, true, ExampleContext::Request)?;
         Ok(quote! {
             .#ident(#value)
         })
-    }).collect::<anyhow::Result<Vec<_>, anyhow::Error>>()?;
+    }).collect::<anyhow::Result<Vec<_>, anyhow::Error>>();
+    let optionals = setters(optionals)?;
+    if !in_params_struct.is_empty() {
+        let params_setters = setters(params_struct_optionals)?;
+        let params_struct = operation.params_struct_name().to_rust_struct();
+        declarations.push(quote! {
+            let params = #params_struct::default()
+                #(#params_setters)*;
+        });
+        fn_args.push("params".to_rust_ident());
+    }
     let qualified_client = format!("{}::{}", opt.package_name, opt.client_name().to_rust_struct());
     let mut imports = vec![
         Import::package(&qualified_client),
@@ -47,7 +62,11 @@ pub fn generate_example(operation: &Operation, opt: &PackageConfig, spec: &HirSp
         let struct_name = operation.required_struct_name().to_rust_struct().to_string();
         imports.push(Import::package(&format!("{}::request::{}", opt.package_name, struct_name)));
     }
-    let operation = operation.name.to_rust_ident();
+    if !in_params_struct.is_empty() {
+        let struct_name = operation.params_struct_name().to_rust_struct().to_string();
+        imports.push(Import::package(&format!("{}::request::{}", opt.package_name, struct_name)));
+    }
+    let operation = method_name(operation, &opt.config);
     let client = opt.client_name().to_rust_struct();
     let mut main = rfunction!(async main() {
         let client = #client::from_env();
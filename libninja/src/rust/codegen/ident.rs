@@ -1,7 +1,121 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use convert_case::{Case, Casing};
+use regex::{Captures, Regex};
+
 use mir::Ident;
 
 use crate::rust::codegen;
 
+thread_local! {
+    /// The same names recur constantly across a spec, so sanitized names are cached by their input.
+    static IDENTS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+    static STRUCTS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+    /// Number of names actually sanitized, i.e. cache misses.
+    static SANITIZED: Cell<usize> = Cell::new(0);
+}
+
+fn cached(
+    cache: &'static std::thread::LocalKey<RefCell<HashMap<String, String>>>,
+    s: &str,
+    sanitize: impl FnOnce(&str) -> String,
+) -> String {
+    if let Some(hit) = cache.with(|c| c.borrow().get(s).cloned()) {
+        return hit;
+    }
+    SANITIZED.with(|n| n.set(n.get() + 1));
+    let value = sanitize(s);
+    cache.with(|c| c.borrow_mut().insert(s.to_string(), value.clone()));
+    value
+}
+
+fn rewrite_names(s: &str) -> String {
+    // custom logic for Github openapi spec lol
+    if s == "+1" {
+        return "PlusOne".to_string();
+    } else if s == "-1" {
+        return "MinusOne".to_string();
+    }
+    s.replace('/', "_")
+        .replace(['@', '\'', '+'], "")
+        .replace(':', " ")
+        .replace('.', "_")
+}
+
+pub(super) fn sanitize(s: impl AsRef<str>) -> String {
+    cached(&IDENTS, s.as_ref(), sanitize_uncached)
+}
+
+pub(super) fn sanitize_struct(s: impl AsRef<str>) -> Ident {
+    Ident(cached(&STRUCTS, s.as_ref(), sanitize_struct_uncached))
+}
+
+fn sanitize_uncached(s: &str) -> String {
+    static NUMBER_SEPARATOR: OnceLock<Regex> = OnceLock::new();
+    let regex = NUMBER_SEPARATOR.get_or_init(|| Regex::new("[a-z]_[0-9]").unwrap());
+    let original = s;
+    let s = rewrite_names(s);
+    let mut s = s.to_case(Case::Snake);
+    s = regex
+        .replace_all(&s, |c: &Captures| {
+            let mut c = c.get(0).unwrap().as_str().to_string();
+            c.remove(1);
+            c
+        })
+        .into();
+    if codegen::is_restricted(&s) {
+        s += "_"
+    }
+    if s.chars().next().unwrap().is_numeric() {
+        s = format!("_{}", s)
+    }
+
+    let s = s.replace('[', "_lsq_")
+        .replace(']', "_rsq_");
+
+    assert_valid_ident(&s, original);
+    s
+}
+
+/// Like `sanitize`, but keeps the original casing, only replacing characters that can't be in an identifier.
+pub(super) fn sanitize_verbatim(s: &str) -> String {
+    let original = s;
+    let mut s: String = rewrite_names(s)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if codegen::is_restricted(&s) {
+        s += "_"
+    }
+    if s.chars().next().unwrap().is_numeric() {
+        s = format!("_{}", s)
+    }
+    assert_valid_ident(&s, original);
+    s
+}
+
+fn sanitize_struct_uncached(s: &str) -> String {
+    let original = s;
+    let s = rewrite_names(s);
+    let mut s = s.to_case(Case::Pascal);
+    if codegen::is_restricted(&s) {
+        s += "Struct"
+    }
+    assert_valid_ident(&s, original);
+    s
+}
+
+fn assert_valid_ident(s: &str, original: &str) {
+    if s.chars().next().map(|c| c.is_numeric()).unwrap_or_default() {
+        panic!("Numeric identifier: {}", original)
+    }
+    if s.contains('.') {
+        panic!("Dot in identifier: {}", original)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -13,4 +127,21 @@ mod tests {
         assert_eq!(String::from(s).to_rust_ident().0, "sd_address_contractor1099");
         assert_eq!(sanitize_filename(s), "sd_address_contractor1099");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sanitize_verbatim() {
+        assert_eq!(sanitize_verbatim("listPets"), "listPets");
+        assert_eq!(sanitize_verbatim("pets.get-by-id"), "pets_get_by_id");
+        assert_eq!(sanitize_verbatim("type"), "type_");
+    }
+
+    #[test]
+    fn test_sanitize_is_cached() {
+        let before = SANITIZED.with(|n| n.get());
+        for _ in 0..1000 {
+            assert_eq!("account-id-v2".to_rust_ident().0, "account_id_v2");
+            assert_eq!("account-id-v2".to_rust_struct().0, "AccountIdV2");
+        }
+        assert_eq!(SANITIZED.with(|n| n.get()) - before, 2);
+    }
+}
@@ -1,8 +1,9 @@
 use proc_macro2::TokenStream;
 use quote::quote;
-use hir::{HirSpec, Ty};
+use hir::{HirSpec, Record, StringFormat, Ty};
 use crate::rust::codegen::ToRustIdent;
 use crate::rust::lower_mir::HirFieldExt;
+use crate::rust::validated::{bounded_map_type_name, multiple_of_type_name, validated_type_name};
 
 /// Use this to generate Rust code types.
 pub trait ToRustType {
@@ -17,13 +18,23 @@ impl ToRustType for Ty {
     fn to_rust_type(&self) -> TokenStream {
         match self {
             Ty::String => quote!(String),
-            Ty::Integer { .. } => quote!(i64),
+            Ty::Char => quote!(char),
+            Ty::Integer { bits, .. } => signed_type(*bits),
+            Ty::Unsigned { bits } => unsigned_type(*bits),
             Ty::Float => quote!(f64),
             Ty::Boolean => quote!(bool),
             Ty::Array(inner) => {
                 let inner = inner.to_rust_type();
                 quote!(Vec<#inner>)
             }
+            Ty::Option(inner) => {
+                let inner = inner.to_rust_type();
+                quote!(Option<#inner>)
+            }
+            Ty::Tuple(items) => {
+                let items = items.iter().map(|ty| ty.to_rust_type());
+                quote!((#(#items,)*))
+            }
             Ty::Model(inner, ..) => {
                 inner.to_rust_struct().into()
             }
@@ -32,13 +43,24 @@ impl ToRustType for Ty {
             Ty::Date { .. } => quote!(chrono::NaiveDate),
             Ty::DateTime { .. } => quote!(chrono::DateTime<chrono::Utc>),
             Ty::Currency { .. } => quote!(rust_decimal::Decimal),
+            Ty::Format(format) => format_to_rust_type(*format),
+            Ty::MultipleOf(multiple) => {
+                let name = multiple_of_type_name(*multiple);
+                quote!(crate::validated::#name)
+            }
+            Ty::BoundedMap(count) => {
+                let name = bounded_map_type_name(*count);
+                quote!(crate::validated::#name)
+            }
         }
     }
 
     fn to_reference_type(&self, specifier: TokenStream) -> TokenStream {
         match self {
             Ty::String => quote!(& #specifier str),
-            Ty::Integer { .. } => quote!(i64),
+            Ty::Char => quote!(char),
+            Ty::Integer { bits, .. } => signed_type(*bits),
+            Ty::Unsigned { bits } => unsigned_type(*bits),
             Ty::Float => quote!(f64),
             Ty::Boolean => quote!(bool),
             Ty::Array(inner) => {
@@ -49,6 +71,7 @@ impl ToRustType for Ty {
                     self.to_rust_type()
                 }
             }
+            Ty::Option(_) | Ty::Tuple(_) => self.to_rust_type(),
             Ty::Model(inner, ..) => {
                 inner.to_rust_struct().into()
             }
@@ -57,6 +80,15 @@ impl ToRustType for Ty {
             Ty::Date { .. } => quote!(chrono::NaiveDate),
             Ty::DateTime { .. } => quote!(chrono::DateTime<chrono::Utc>),
             Ty::Currency { .. } => quote!(rust_decimal::Decimal),
+            Ty::Format(format) => format_to_rust_type(*format),
+            Ty::MultipleOf(multiple) => {
+                let name = multiple_of_type_name(*multiple);
+                quote!(crate::validated::#name)
+            }
+            Ty::BoundedMap(count) => {
+                let name = bounded_map_type_name(*count);
+                quote!(crate::validated::#name)
+            }
         }
     }
 
@@ -72,40 +104,84 @@ impl ToRustType for Ty {
     fn implements_default(&self, spec: &HirSpec) -> bool {
         match self {
             Ty::String => true,
+            Ty::Char => true,
             Ty::Integer { .. } => true,
+            Ty::Unsigned { .. } => true,
             Ty::Float => true,
             Ty::Boolean => true,
             Ty::Array(_) => true,
+            Ty::Option(_) => true,
+            Ty::Tuple(items) => items.iter().all(|ty| ty.implements_default(spec)),
             Ty::Model(name) => {
                 let model = spec.get_record(name.as_str()).expect("Model not found");
-                model.fields().all(|f| f.implements_default(spec))
+                !matches!(model, Record::Union(_)) && model.fields().all(|f| f.implements_default(spec))
             }
             Ty::Unit => true,
             Ty::Any => true,
             Ty::Date { .. } => true,
-            Ty::DateTime => true,
+            Ty::DateTime { .. } => true,
             Ty::Currency { .. } => true,
+            Ty::Format(format) => *format == StringFormat::Password,
+            Ty::MultipleOf(_) => false,
+            Ty::BoundedMap(_) => false,
         }
     }
 
     fn implements_dummy(&self, spec: &HirSpec) -> bool {
         match self {
             Ty::String => true,
+            Ty::Char => true,
             Ty::Integer { .. } => true,
+            Ty::Unsigned { .. } => true,
             Ty::Float => true,
             Ty::Boolean => true,
             Ty::Array(inner) => {
                 inner.implements_dummy(spec)
             }
+            Ty::Option(inner) => inner.implements_dummy(spec),
+            Ty::Tuple(items) => items.iter().all(|ty| ty.implements_dummy(spec)),
             Ty::Model(name) => {
                 let model = spec.get_record(name.as_str()).expect("Model not found");
-                model.fields().all(|f| f.ty.implements_dummy(spec))
+                !matches!(model, Record::Union(_)) && model.fields().all(|f| f.ty.implements_dummy(spec))
             }
             Ty::Unit => true,
             Ty::Any => false,
             Ty::Date { .. } => true,
-            Ty::DateTime => true,
+            Ty::DateTime { .. } => true,
             Ty::Currency { .. } => true,
+            Ty::Format(_) => false,
+            Ty::MultipleOf(_) => false,
+            Ty::BoundedMap(_) => false,
+        }
+    }
+}
+
+fn signed_type(bits: u8) -> TokenStream {
+    match bits {
+        8 => quote!(i8),
+        16 => quote!(i16),
+        32 => quote!(i32),
+        _ => quote!(i64),
+    }
+}
+
+fn unsigned_type(bits: u8) -> TokenStream {
+    match bits {
+        8 => quote!(u8),
+        16 => quote!(u16),
+        32 => quote!(u32),
+        _ => quote!(u64),
+    }
+}
+
+fn format_to_rust_type(format: StringFormat) -> TokenStream {
+    match format {
+        StringFormat::Ipv4 => quote!(std::net::Ipv4Addr),
+        StringFormat::Ipv6 => quote!(std::net::Ipv6Addr),
+        StringFormat::Password => quote!(String),
+        _ => {
+            let name = validated_type_name(format);
+            quote!(crate::validated::#name)
         }
     }
 }
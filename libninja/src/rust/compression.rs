@@ -0,0 +1,125 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Emits the items into the generated crate, and also compiles them here for the tests, so the
+/// tests run the same code the generated client does.
+macro_rules! generated_and_tested {
+    ($tokens:ident, $module:ident, $($item:tt)*) => {
+        fn $tokens() -> TokenStream {
+            quote!($($item)*)
+        }
+
+        #[cfg(test)]
+        mod $module {
+            $($item)*
+        }
+    };
+}
+
+generated_and_tested! {
+    gzip_if_larger, gzip,
+
+    /// Gzips `bytes` if there are more than `threshold` of them. Smaller bodies are sent as-is.
+    pub(crate) fn gzip_if_larger(bytes: &[u8], threshold: usize) -> Option<Vec<u8>> {
+        use std::io::Write;
+
+        if bytes.len() <= threshold {
+            return None;
+        }
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).expect("writing to a Vec cannot fail");
+        Some(encoder.finish().expect("writing to a Vec cannot fail"))
+    }
+}
+
+/// Generate the compression.rs file, used when `gzip_requests` is enabled.
+pub fn generate_compression_rs() -> TokenStream {
+    let gzip_if_larger = gzip_if_larger();
+    quote! {
+        use httpclient::{InMemoryBody, RequestBuilder};
+
+        #gzip_if_larger
+
+        /// Gzip the request body if it is larger than `threshold` bytes. Smaller bodies are sent as-is.
+        pub(crate) fn gzip_request_body(mut r: RequestBuilder<'_>, threshold: usize) -> RequestBuilder<'_> {
+            let bytes = match &r.body {
+                Some(InMemoryBody::Json(value)) => serde_json::to_vec(value).expect("json body is serializable"),
+                Some(InMemoryBody::Text(text)) => text.as_bytes().to_vec(),
+                Some(InMemoryBody::Bytes(bytes)) => bytes.clone(),
+                _ => return r,
+            };
+            let Some(gzipped) = gzip_if_larger(&bytes, threshold) else {
+                return r;
+            };
+            r.body = Some(InMemoryBody::Bytes(gzipped));
+            r.header("Content-Encoding", "gzip")
+        }
+    }
+}
+
+/// Compresses the request body before it is sent, if the client has a gzip threshold set.
+pub fn gzip_request_body() -> TokenStream {
+    quote! {
+        #[cfg(feature = "gzip")]
+        if let Some(threshold) = self.client.gzip_threshold {
+            r = crate::compression::gzip_request_body(r, threshold);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use hir::{HirSpec, Operation};
+    use ln_core::ConfigFlags;
+
+    use crate::rust::format::format_code;
+    use crate::rust::request::impl_send_request;
+    use crate::rust::test_util::petstore_opt;
+
+    use super::*;
+
+    #[test]
+    fn test_gzip_request_body() {
+        let code = format_code(generate_compression_rs()).unwrap();
+        assert!(code.contains("pub(crate) fn gzip_if_larger(bytes: &[u8], threshold: usize) -> Option<Vec<u8>> {"));
+        assert!(code.contains("let Some(gzipped) = gzip_if_larger(&bytes, threshold) else {"));
+        assert!(code.contains("r.header(\"Content-Encoding\", \"gzip\")"));
+    }
+
+    #[test]
+    fn test_gzip_call_site() {
+        let operation = Operation {
+            name: "createPet".to_string(),
+            method: "post".to_string(),
+            path: "/pets".to_string(),
+            ..Operation::default()
+        };
+        let mut opt = petstore_opt(ConfigFlags::default());
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(!code.contains("gzip"));
+
+        opt.config.gzip_requests = true;
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        let gate = code.find("#[cfg(feature = \"gzip\")]").unwrap();
+        let check = code.find("if let Some(threshold) = self.client.gzip_threshold {").unwrap();
+        assert!(gate < check);
+        assert!(code.contains("r = crate::compression::gzip_request_body(r, threshold);"));
+        // Compression comes before signing, so the signer sees the body that is sent.
+        assert!(check < code.find("signer.sign(r)").unwrap());
+    }
+
+    #[test]
+    fn test_gzip_threshold() {
+        let large = "pet ".repeat(512).into_bytes();
+        let gzipped = gzip::gzip_if_larger(&large, 1024).unwrap();
+        assert!(gzipped.len() < large.len());
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(gzipped.as_slice()).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, large);
+
+        assert!(gzip::gzip_if_larger(b"{\"name\": \"Rex\"}", 1024).is_none());
+        assert!(gzip::gzip_if_larger(&large[..1024], 1024).is_none());
+    }
+}
@@ -0,0 +1,366 @@
+use proc_macro2::{Literal, TokenStream};
+use quote::{quote, ToTokens};
+
+use hir::{ErrorStatus, HirSpec};
+use ln_core::ConfigFlags;
+
+use crate::rust::codegen::ToRustIdent;
+
+/// Generate the error.rs file. Requests return a `ClientError` so that failures name the operation they came from.
+pub fn generate_error_rs(spec: &HirSpec, config: &ConfigFlags) -> TokenStream {
+    let validation_error = config.validate.then(validation_error).unwrap_or_default();
+    let api_error = config.shared_errors.then(|| api_error(spec)).unwrap_or_default();
+    let thiserror = config.thiserror;
+    let error = |message: TokenStream| thiserror.then(|| quote!(#[error(#message)]));
+    let derive = if thiserror {
+        quote!(#[derive(Debug, thiserror::Error)])
+    } else {
+        quote!(#[derive(Debug)])
+    };
+    let http_error = error(quote!("{operation} failed with status {status}: {}", snippet(.body)));
+    let protocol_error = error(quote!("{operation} failed: {source}"));
+    let scopes = config.check_scopes;
+    let missing_scopes_error = error(quote!("{operation} needs scopes the token doesn't have: {}", .missing.join(", ")));
+    let missing_scopes = scopes.then(|| quote! {
+        /// The client's token lacks scopes the operation needs, so the request wasn't sent.
+        #missing_scopes_error
+        MissingScopes {
+            operation: &'static str,
+            missing: Vec<String>,
+        },
+    });
+    let missing_scopes_operation = scopes.then(|| quote!(ClientError::MissingScopes { operation, .. } => operation,));
+    let missing_scopes_status = scopes.then(|| quote!(ClientError::MissingScopes { .. } => None,));
+    let missing_scopes_retryable = scopes.then(|| quote!(ClientError::MissingScopes { .. } => false,));
+    let missing_scopes_display = scopes.then(|| quote! {
+        ClientError::MissingScopes { operation, missing } => {
+            write!(f, "{} needs scopes the token doesn't have: {}", operation, missing.join(", "))
+        }
+    });
+    let missing_scopes_source = scopes.then(|| quote!(ClientError::MissingScopes { .. } => None,));
+    let cancel = config.cancellation;
+    let cancelled_error = error(quote!("{operation} was cancelled"));
+    let cancelled = cancel.then(|| quote! {
+        /// The request's `CancellationToken` was cancelled before a response arrived.
+        #[cfg(feature = "cancellation")]
+        #cancelled_error
+        Cancelled {
+            operation: &'static str,
+        },
+    });
+    let cancelled_operation = cancel.then(|| quote! {
+        #[cfg(feature = "cancellation")]
+        ClientError::Cancelled { operation } => operation,
+    });
+    let cancelled_status = cancel.then(|| quote! {
+        #[cfg(feature = "cancellation")]
+        ClientError::Cancelled { .. } => None,
+    });
+    let cancelled_retryable = cancel.then(|| quote! {
+        #[cfg(feature = "cancellation")]
+        ClientError::Cancelled { .. } => false,
+    });
+    let cancelled_display = cancel.then(|| quote! {
+        #[cfg(feature = "cancellation")]
+        ClientError::Cancelled { operation } => write!(f, "{} was cancelled", operation),
+    });
+    let cancelled_source = cancel.then(|| quote! {
+        #[cfg(feature = "cancellation")]
+        ClientError::Cancelled { .. } => None,
+    });
+    // With thiserror, the `#[error]` attributes stand in for these.
+    let error_impls = (!thiserror).then(|| quote! {
+        impl fmt::Display for ClientError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    ClientError::Http { operation, status, body } => {
+                        write!(f, "{} failed with status {}: {}", operation, status, snippet(body))
+                    }
+                    ClientError::Protocol { operation, source } => {
+                        write!(f, "{} failed: {}", operation, source)
+                    }
+                    #missing_scopes_display
+                    #cancelled_display
+                }
+            }
+        }
+
+        impl std::error::Error for ClientError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                match self {
+                    ClientError::Http { .. } => None,
+                    ClientError::Protocol { source, .. } => Some(source),
+                    #missing_scopes_source
+                    #cancelled_source
+                }
+            }
+        }
+    });
+    let fmt = (!thiserror || config.validate).then(|| quote!(use std::fmt;));
+    quote! {
+        #fmt
+        use httpclient::InMemoryResponseExt;
+
+        /// Error bodies longer than this are truncated in `Display` output.
+        const MAX_BODY_SNIPPET: usize = 200;
+
+        #derive
+        pub enum ClientError {
+            /// The server responded with an unsuccessful status code.
+            #http_error
+            Http {
+                operation: &'static str,
+                status: u16,
+                body: String,
+            },
+            /// The request failed before a response was received, or the response couldn't be read.
+            #protocol_error
+            Protocol {
+                operation: &'static str,
+                source: httpclient::ProtocolError,
+            },
+            #missing_scopes
+            #cancelled
+        }
+
+        impl ClientError {
+            pub(crate) fn from_httpclient(operation: &'static str, error: httpclient::Error) -> Self {
+                match error {
+                    httpclient::Error::HttpError(res) => {
+                        let status = res.status().as_u16();
+                        let body = res.text().unwrap_or_default();
+                        ClientError::Http { operation, status, body }
+                    }
+                    httpclient::Error::Protocol(source) => ClientError::Protocol { operation, source },
+                }
+            }
+
+            /// The operation that failed.
+            pub fn operation(&self) -> &'static str {
+                match self {
+                    ClientError::Http { operation, .. } => operation,
+                    ClientError::Protocol { operation, .. } => operation,
+                    #missing_scopes_operation
+                    #cancelled_operation
+                }
+            }
+
+            /// The status code, if the server responded.
+            pub fn status(&self) -> Option<u16> {
+                match self {
+                    ClientError::Http { status, .. } => Some(*status),
+                    ClientError::Protocol { .. } => None,
+                    #missing_scopes_status
+                    #cancelled_status
+                }
+            }
+
+            /// Whether the request might succeed if sent again: connection errors and timeouts,
+            /// 429 Too Many Requests, and 5xx server errors.
+            pub fn is_retryable(&self) -> bool {
+                match self {
+                    ClientError::Http { status, .. } => *status == 429 || (500..=599).contains(status),
+                    ClientError::Protocol { source, .. } => matches!(
+                        source,
+                        httpclient::ProtocolError::ConnectionError(_) | httpclient::ProtocolError::IoError(_)
+                    ),
+                    #missing_scopes_retryable
+                    #cancelled_retryable
+                }
+            }
+        }
+
+        fn snippet(body: &str) -> &str {
+            match body.char_indices().nth(MAX_BODY_SNIPPET) {
+                Some((i, _)) => &body[..i],
+                None => body,
+            }
+        }
+
+        #error_impls
+
+        #validation_error
+        #api_error
+    }
+}
+
+/// One enum for the documented error bodies of every operation. `ClientError::api_error` parses the body
+/// with the schemas that the failed operation documents for the status it got.
+fn api_error(spec: &HirSpec) -> TokenStream {
+    let models = spec.error_models();
+    if models.is_empty() {
+        return TokenStream::new();
+    }
+    let variants = models.iter().map(|model| {
+        let model = model.to_rust_struct();
+        quote!(#model(crate::model::#model))
+    });
+    let arms = spec.operations.iter().flat_map(|operation| {
+        let name = operation.name.as_str();
+        operation.errors.iter().filter_map(move |error| {
+            let model = error.ty.inner_model().filter(|_| !error.ty.is_iterable())?.to_rust_struct();
+            let status = match error.status {
+                ErrorStatus::Code(code) => Literal::u16_unsuffixed(code).into_token_stream(),
+                ErrorStatus::Range(digit) => {
+                    let start = Literal::u16_unsuffixed(digit * 100);
+                    let end = Literal::u16_unsuffixed(digit * 100 + 99);
+                    quote!(#start..=#end)
+                }
+                ErrorStatus::Default => quote!(_),
+            };
+            Some(quote! {
+                (#name, #status) => serde_json::from_str(body).ok().map(ApiError::#model),
+            })
+        })
+    });
+    quote! {
+        #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+        #[serde(untagged)]
+        pub enum ApiError {
+            #(#variants,)*
+        }
+
+        impl ClientError {
+            /// The parsed error body, if the operation documents one for the response status.
+            /// Use `operation()` to tell which operation it came from.
+            pub fn api_error(&self) -> Option<ApiError> {
+                let ClientError::Http { operation, status, body } = self else {
+                    return None;
+                };
+                match (*operation, *status) {
+                    #(#arms)*
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// Returned by the generated `validate()` methods when a model breaks a rule from the spec.
+fn validation_error() -> TokenStream {
+    quote! {
+        #[derive(Debug)]
+        pub struct ValidationError {
+            pub field: &'static str,
+            pub message: String,
+        }
+
+        impl fmt::Display for ValidationError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "invalid `{}`: {}", self.field, self.message)
+            }
+        }
+
+        impl std::error::Error for ValidationError {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hir::{ErrorResponse, Operation, Ty};
+
+    use crate::rust::format::format_code;
+
+    use super::*;
+
+    #[test]
+    fn test_error_display() {
+        let code = format_code(generate_error_rs(&HirSpec::default(), &ConfigFlags::default())).unwrap();
+        assert!(code.contains("impl fmt::Display for ClientError {"));
+        assert!(code.contains("impl std::error::Error for ClientError {"));
+        assert!(code.contains("\"{} failed with status {}: {}\", operation, status, snippet(body)"));
+        assert!(code.contains("\"{} failed: {}\", operation, source"));
+        assert!(!code.contains("ValidationError"));
+    }
+
+    #[test]
+    fn test_thiserror_derive() {
+        let config = ConfigFlags {
+            thiserror: true,
+            check_scopes: true,
+            ..ConfigFlags::default()
+        };
+        let code = format_code(generate_error_rs(&HirSpec::default(), &config)).unwrap();
+        assert!(code.contains("#[derive(Debug, thiserror::Error)]\npub enum ClientError {"));
+        assert!(code.contains("#[error(\"{operation} failed with status {status}: {}\","));
+        assert!(code.contains("#[error(\"{operation} failed: {source}\")]"));
+        assert!(code.contains("#[error(\"{operation} needs scopes the token doesn't have: {}\","));
+        assert!(!code.contains("impl fmt::Display for ClientError {"));
+        assert!(!code.contains("impl std::error::Error for ClientError {"));
+        assert!(!code.contains("use std::fmt;"));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let code = format_code(generate_error_rs(&HirSpec::default(), &ConfigFlags::default())).unwrap();
+        assert!(code.contains("pub fn is_retryable(&self) -> bool {"));
+        assert!(code.contains("*status == 429 || (500..=599).contains(status)"));
+        assert!(code.contains("httpclient::ProtocolError::ConnectionError(_)"));
+    }
+
+    #[test]
+    fn test_shared_api_error() {
+        let error = |status, model: &str| ErrorResponse { status, ty: Ty::model(model) };
+        let spec = HirSpec {
+            operations: vec![
+                Operation {
+                    name: "getPet".to_string(),
+                    errors: vec![error(ErrorStatus::Code(404), "NotFound"), error(ErrorStatus::Default, "Problem")],
+                    ..Operation::default()
+                },
+                Operation {
+                    name: "createPet".to_string(),
+                    errors: vec![error(ErrorStatus::Range(4), "ValidationError")],
+                    ..Operation::default()
+                },
+            ],
+            ..HirSpec::default()
+        };
+        let config = ConfigFlags {
+            shared_errors: true,
+            ..ConfigFlags::default()
+        };
+        let code = format_code(generate_error_rs(&spec, &config)).unwrap();
+        assert!(code.contains("pub enum ApiError {\n    NotFound(crate::model::NotFound),\n    Problem(crate::model::Problem),\n    ValidationError(crate::model::ValidationError),\n}"));
+        assert!(code.contains("(\"getPet\", 404) => serde_json::from_str(body).ok().map(ApiError::NotFound),"));
+        assert!(code.contains("(\"getPet\", _) => serde_json::from_str(body).ok().map(ApiError::Problem),"));
+        // Any status other than 404 gets the `default` schema.
+        assert!(code.find("(\"getPet\", 404)").unwrap() < code.find("(\"getPet\", _)").unwrap());
+        assert!(code.contains("(\"createPet\", 400..=499) => serde_json::from_str(body).ok().map(ApiError::ValidationError),"));
+    }
+
+    #[test]
+    fn test_missing_scopes_error() {
+        let code = format_code(generate_error_rs(&HirSpec::default(), &ConfigFlags::default())).unwrap();
+        assert!(!code.contains("MissingScopes"));
+        let config = ConfigFlags {
+            check_scopes: true,
+            ..ConfigFlags::default()
+        };
+        let code = format_code(generate_error_rs(&HirSpec::default(), &config)).unwrap();
+        assert!(code.contains("MissingScopes {"));
+        assert!(code.contains("missing: Vec<String>"));
+        assert!(code.contains("ClientError::MissingScopes { .. } => false,"));
+    }
+
+    #[test]
+    fn test_cancelled_error() {
+        let config = ConfigFlags {
+            cancellation: true,
+            ..ConfigFlags::default()
+        };
+        let code = format_code(generate_error_rs(&HirSpec::default(), &config)).unwrap();
+        assert!(code.contains("#[cfg(feature = \"cancellation\")]\n    Cancelled {"));
+        assert!(code.contains("ClientError::Cancelled { operation } => write!(f, \"{} was cancelled\", operation),"));
+    }
+
+    #[test]
+    fn test_validation_error() {
+        let config = ConfigFlags {
+            validate: true,
+            ..ConfigFlags::default()
+        };
+        let code = format_code(generate_error_rs(&HirSpec::default(), &config)).unwrap();
+        assert!(code.contains("pub struct ValidationError {"));
+    }
+}
@@ -38,6 +38,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_formatted_file_parses_back() {
+        use mir::{File, Import};
+        use crate::rust::codegen::ToRustCode;
+
+        let file = File {
+            imports: vec![Import::new("serde", vec!["Serialize", "Deserialize"])],
+            code: Some(quote! {
+                #[derive(Debug, Serialize, Deserialize)]
+                pub struct Pet { pub name: String, pub tags: Vec<String> }
+            }),
+            ..File::default()
+        };
+        let code = format_code(file.to_rust_code()).unwrap();
+        assert!(code.contains("pub struct Pet {\n    pub name: String,"));
+        // prettyplease only ever emits valid syntax, so the output parses back, without needing rustfmt.
+        syn::parse_file(&code).unwrap();
+    }
+
     #[test]
     fn test_codegen() {
         let code = codegen_example();
@@ -1,12 +1,13 @@
 use std::collections::BTreeSet;
 
-use convert_case::Casing;
+use convert_case::{Case, Casing};
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
 
-use hir::{DateSerialization, DecimalSerialization, HirField, HirSpec, IntegerSerialization, NewType, Record, StrEnum, Struct, Ty, TypeAlias};
+use hir::{DateSerialization, DateTimeSerialization, DecimalSerialization, HirField, HirSpec, IntegerSerialization, NewType, Record, StrEnum, Struct, Ty, TypeAlias, Union};
 use ln_core::ConfigFlags;
 use mir::{Field, File, Ident, Import, import, Visibility};
+use tracing::warn;
 
 use crate::rust::codegen;
 use crate::rust::codegen::{sanitize_filename, ToRustCode};
@@ -14,23 +15,25 @@ use crate::rust::codegen::ToRustIdent;
 use crate::rust::codegen::ToRustType;
 
 pub trait FieldExt {
-    fn decorators(&self, name: &str, config: &ConfigFlags) -> Vec<TokenStream>;
+    fn decorators(&self, name: &str, config: &ConfigFlags, spec: &HirSpec) -> Vec<TokenStream>;
 }
 
 impl FieldExt for HirField {
-    fn decorators(&self, name: &str, config: &ConfigFlags) -> Vec<TokenStream> {
+    fn decorators(&self, name: &str, config: &ConfigFlags, spec: &HirSpec) -> Vec<TokenStream> {
+        // Internal fields never go over the wire, so no other serde attribute applies.
+        if self.internal {
+            return vec![quote!(#[serde(skip)])];
+        }
         let mut decorators = Vec::new();
         let rust_ident = name.to_rust_ident();
+        if self.flatten {
+            decorators.push(quote! {
+                #[serde(flatten)]
+            });
+        }
         if rust_ident.0 != name {
-            let name = &name;
-            if self.flatten {
-                decorators.push(quote! {
-                    #[serde(flatten)]
-                });
-            } else {
-                decorators.push(quote! {
-                    #[serde(rename = #name)]
-                });
+            if !self.flatten {
+                decorators.push(codegen::serde_rename(name, &rust_ident));
             }
             if config.ormlite {
                 decorators.push(quote! {
@@ -38,6 +41,28 @@ impl FieldExt for HirField {
                 });
             }
         }
+        for alias in &self.aliases {
+            decorators.push(quote! {
+                #[serde(alias = #alias)]
+            });
+        }
+        // One struct serves both directions: the server assigns `readOnly` fields, so they're never
+        // sent, and never returns `writeOnly` ones, so they're never read. Skipped fields are
+        // filled with `Default`, which validated and model types might not implement.
+        if self.read_only {
+            decorators.push(quote! {
+                #[serde(skip_serializing)]
+            });
+        }
+        if self.write_only {
+            if self.implements_default(spec) {
+                decorators.push(quote! {
+                    #[serde(skip_deserializing)]
+                });
+            } else {
+                warn!("Field {} is writeOnly, but its type has no Default, so it is still deserialized", name);
+            }
+        }
         if self.optional {
             decorators.push(quote! {
                 #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -51,13 +76,24 @@ impl FieldExt for HirField {
                 #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
             });
         }
+        if self.comma_separated {
+            if self.optional {
+                decorators.push(quote! {
+                    #[serde(with = "crate::serde::option_comma_separated")]
+                });
+            } else {
+                decorators.push(quote! {
+                    #[serde(with = "crate::serde::comma_separated")]
+                });
+            }
+        }
         if self.ty.inner_model().is_some() && config.ormlite {
             decorators.push(quote! {
                 #[cfg_attr(feature = "ormlite", ormlite(experimental_encode_as_json))]
             });
         }
         match self.ty {
-            Ty::Integer { serialization } => {
+            Ty::Integer { serialization, .. } => {
                 match serialization {
                     IntegerSerialization::Simple => {}
                     IntegerSerialization::String => {
@@ -82,6 +118,17 @@ impl FieldExt for HirField {
                     }
                 }
             }
+            Ty::DateTime { serialization: DateTimeSerialization::UnixSeconds } => {
+                if self.optional {
+                    decorators.push(quote! {
+                        #[serde(with = "chrono::serde::ts_seconds_option")]
+                    });
+                } else {
+                    decorators.push(quote! {
+                        #[serde(with = "chrono::serde::ts_seconds")]
+                    });
+                }
+            }
             Ty::Currency { serialization: DecimalSerialization::String } => {
                 if self.optional {
                     decorators.push(quote! {
@@ -102,8 +149,10 @@ impl FieldExt for HirField {
 pub trait StructExt {
     fn implements_default(&self, spec: &HirSpec) -> bool;
     fn derive_default(&self, spec: &HirSpec) -> TokenStream;
-    fn model_fields<'a>(&'a self, config: &'a ConfigFlags) -> Box<dyn Iterator<Item=Field<TokenStream>> + 'a>;
+    fn model_fields<'a>(&'a self, config: &'a ConfigFlags, spec: &'a HirSpec) -> Box<dyn Iterator<Item=Field<TokenStream>> + 'a>;
     fn ref_target(&self) -> Option<RefTarget>;
+    /// The only required array field, if there is exactly one. The struct is then a list with some metadata.
+    fn list_field(&self) -> Option<(&String, &HirField)>;
 }
 
 impl StructExt for Struct {
@@ -119,26 +168,16 @@ impl StructExt for Struct {
         }
     }
 
-    fn model_fields<'a>(&'a self, config: &'a ConfigFlags) -> Box<dyn Iterator<Item=Field<TokenStream>> + 'a> {
+    fn model_fields<'a>(&'a self, config: &'a ConfigFlags, spec: &'a HirSpec) -> Box<dyn Iterator<Item=Field<TokenStream>> + 'a> {
         Box::new(self.fields.iter().map(|(name, field)| {
-            let decorators = field.decorators(name, config);
+            let decorators = field.decorators(name, config, spec);
             let ty = field.ty.to_rust_type();
-            let mut optional = field.optional;
-            match field.ty {
-                Ty::Integer { serialization: IntegerSerialization::NullAsZero | IntegerSerialization::String } => {
-                    optional = true;
-                }
-                Ty::Date { serialization: DateSerialization::Integer } => {
-                    optional = true;
-                }
-                _ => {}
-            }
             Field {
                 name: name.clone(),
                 ty,
                 visibility: Visibility::Public,
                 decorators,
-                optional,
+                optional: field.is_rust_option(),
                 doc: field.doc.clone(),
                 ..Field::default()
             }
@@ -153,6 +192,12 @@ impl StructExt for Struct {
             }
         })
     }
+
+    fn list_field(&self) -> Option<(&String, &HirField)> {
+        let mut lists = self.fields.iter().filter(|(_, f)| matches!(f.ty, Ty::Array(_)) && !f.is_rust_option());
+        let list = lists.next()?;
+        lists.next().is_none().then_some(list)
+    }
 }
 
 pub trait RecordExt {
@@ -162,7 +207,7 @@ pub trait RecordExt {
 impl RecordExt for Record {
     fn imports(&self, path: &str) -> Option<Import> {
         let names = self.fields()
-            .flat_map(|f| f.ty.inner_model())
+            .flat_map(|f| f.ty.inner_models())
             .filter(|&name| name != self.name())
             .map(|name| name.to_rust_struct().0)
             .collect::<BTreeSet<_>>();
@@ -176,12 +221,22 @@ impl RecordExt for Record {
 
 pub trait HirFieldExt {
     fn implements_default(&self, spec: &HirSpec) -> bool;
+    /// Whether the generated field is wrapped in an `Option`.
+    fn is_rust_option(&self) -> bool;
 }
 
 impl HirFieldExt for HirField {
     fn implements_default(&self, spec: &HirSpec) -> bool {
         self.optional || self.ty.implements_default(spec)
     }
+
+    fn is_rust_option(&self) -> bool {
+        match self.ty {
+            Ty::Integer { serialization: IntegerSerialization::NullAsZero | IntegerSerialization::String, .. } => true,
+            Ty::Date { serialization: DateSerialization::Integer } => true,
+            _ => self.optional,
+        }
+    }
 }
 
 /// Generate a model.rs file that just imports from dependents.
@@ -203,6 +258,16 @@ pub fn generate_model_rs(spec: &HirSpec, config: &ConfigFlags) -> File<TokenStre
     }
 }
 
+/// Generate model.rs with every model in it, instead of a file per model.
+pub fn generate_combined_model_rs(spec: &HirSpec, config: &ConfigFlags) -> File<TokenStream> {
+    let code = spec.schemas.values().map(|record| create_struct(record, config, spec)).collect();
+    File {
+        imports: vec![import!("serde", Serialize, Deserialize)],
+        code: Some(code),
+        ..File::default()
+    }
+}
+
 /// Generate the file for a single struct.
 pub fn generate_single_model_file(name: &str, record: &Record, spec: &HirSpec, config: &ConfigFlags) -> File<TokenStream> {
     let mut imports = vec![
@@ -236,7 +301,7 @@ pub fn create_sumtype_struct(schema: &Struct, config: &ConfigFlags, spec: &HirSp
     let docs = schema.docs.clone().to_rust_code();
 
     let name = schema.name.to_rust_struct();
-    let fields = schema.model_fields(config).map(ToRustCode::to_rust_code);
+    let fields = schema.model_fields(config, spec).map(ToRustCode::to_rust_code);
     let deref = schema.ref_target().map(|t| {
         let target = t.name.to_rust_ident();
         let ty = t.ty.to_rust_type();
@@ -255,6 +320,10 @@ pub fn create_sumtype_struct(schema: &Struct, config: &ConfigFlags, spec: &HirSp
         }
     }).unwrap_or_default();
 
+    let validate = config.validate.then(|| create_validate_fn(schema)).flatten().unwrap_or_default();
+    let new = create_new_fn(schema, spec).unwrap_or_default();
+    let list_impls = create_list_impls(schema, spec).unwrap_or_default();
+
     quote! {
         #docs
         #ormlite
@@ -269,33 +338,309 @@ pub fn create_sumtype_struct(schema: &Struct, config: &ConfigFlags, spec: &HirSp
             }
         }
         #deref
+        #new
+        #list_impls
+        #validate
     }
 }
 
+/// `Extend` and `FromIterator` over the list of a response that's a list with some metadata.
+/// `FromIterator` leaves the other fields at their defaults, so it's skipped if one of them has no default.
+fn create_list_impls(schema: &Struct, spec: &HirSpec) -> Option<TokenStream> {
+    let (list_name, list_field) = schema.list_field()?;
+    let Ty::Array(item) = &list_field.ty else { return None; };
+    let is_response = spec.operations.iter().any(|o| matches!(&o.ret, Ty::Model(m) if *m == schema.name));
+    if !is_response {
+        return None;
+    }
+    let name = schema.name.to_rust_struct();
+    let list = list_name.to_rust_ident();
+    let item = item.to_rust_type();
+    let rest = schema.fields.iter()
+        .filter(|(n, _)| *n != list_name)
+        .map(|(n, f)| {
+            let ident = n.to_rust_ident();
+            if f.is_rust_option() {
+                Some(quote!(#ident: None))
+            } else if f.ty.implements_default(spec) {
+                Some(quote!(#ident: Default::default()))
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<_>>>();
+    let from_iter = rest.map(|rest| quote! {
+        impl FromIterator<#item> for #name {
+            fn from_iter<I: IntoIterator<Item = #item>>(iter: I) -> Self {
+                Self {
+                    #list: iter.into_iter().collect(),
+                    #(#rest,)*
+                }
+            }
+        }
+    }).unwrap_or_default();
+    Some(quote! {
+        impl Extend<#item> for #name {
+            fn extend<I: IntoIterator<Item = #item>>(&mut self, iter: I) {
+                self.#list.extend(iter)
+            }
+        }
+        #from_iter
+    })
+}
+
+/// `new` takes the required fields. Server-assigned `readOnly` fields are left at their defaults,
+/// so `new` is skipped if one of them has no default.
+fn create_new_fn(schema: &Struct, spec: &HirSpec) -> Option<TokenStream> {
+    let mut args = Vec::new();
+    let mut values = Vec::new();
+    for (name, field) in &schema.fields {
+        let ident = name.to_rust_ident();
+        if field.is_rust_option() {
+            values.push(quote!(#ident: None));
+        } else if field.read_only {
+            if !field.ty.implements_default(spec) {
+                return None;
+            }
+            values.push(quote!(#ident: Default::default()));
+        } else {
+            let ty = field.ty.to_rust_type();
+            args.push(quote!(#ident: #ty));
+            values.push(quote!(#ident));
+        }
+    }
+    let name = schema.name.to_rust_struct();
+    Some(quote! {
+        impl #name {
+            pub fn new(#(#args),*) -> Self {
+                Self {
+                    #(#values,)*
+                }
+            }
+        }
+    })
+}
 
-fn create_enum_struct(e: &StrEnum) -> TokenStream {
-    let enums = e.variants.iter().filter(|s| !s.is_empty()).map(|s| {
+/// Checks: for each `dependentRequired` rule, if the key field is set, the dependent fields must be set too.
+/// Required fields are always present, so they never need a check.
+fn validate_checks(schema: &Struct) -> Vec<TokenStream> {
+    let mut checks = Vec::new();
+    for (trigger, dependents) in &schema.dependent_required {
+        let Some(trigger_field) = schema.fields.get(trigger) else { continue; };
+        let trigger_ident = trigger.to_rust_ident();
+        let present = if trigger_field.is_rust_option() {
+            quote!(self.#trigger_ident.is_some())
+        } else {
+            quote!(true)
+        };
+        for dependent in dependents {
+            let Some(field) = schema.fields.get(dependent) else { continue; };
+            if !field.is_rust_option() {
+                continue;
+            }
+            let ident = dependent.to_rust_ident();
+            let message = format!("required when `{}` is present", trigger);
+            checks.push(quote! {
+                if #present && self.#ident.is_none() {
+                    return Err(crate::ValidationError {
+                        field: #dependent,
+                        message: #message.to_string(),
+                    });
+                }
+            });
+        }
+    }
+    checks
+}
+
+fn create_validate_fn(schema: &Struct) -> Option<TokenStream> {
+    let checks = validate_checks(schema);
+    if checks.is_empty() {
+        return None;
+    }
+    let name = schema.name.to_rust_struct();
+    Some(quote! {
+        impl #name {
+            pub fn validate(&self) -> Result<(), crate::ValidationError> {
+                #(#checks)*
+                Ok(())
+            }
+        }
+    })
+}
+
+
+fn create_enum_struct(e: &StrEnum, config: &ConfigFlags) -> TokenStream {
+    if config.single_variant_marker {
+        if let Some(value) = e.single_variant() {
+            return create_marker_struct(&e.name, value);
+        }
+    }
+    // Values like `self` and `Self` both sanitize to `SelfStruct`. Later ones get a numeric suffix,
+    // in declaration order, and the rename keeps the original value on the wire.
+    let mut seen = BTreeSet::new();
+    let variants = e.variants.iter().filter(|s| !s.is_empty()).map(|s| {
         let original_name = s.to_string();
         let mut s = original_name.clone();
         if !s.is_empty() && s.chars().next().unwrap().is_numeric() {
             s = format!("{}{}", e.name, s);
         }
-        let name = s.to_rust_struct();
-        let serde_attr = codegen::serde_rename(&original_name, &name);
+        let base = s.to_rust_struct();
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while !seen.insert(name.0.clone()) {
+            name = Ident(format!("{}{}", base.0, suffix));
+            suffix += 1;
+        }
+        (original_name, name)
+    }).collect::<Vec<_>>();
+    let screaming_snake = uses_screaming_snake_variants(&variants);
+    let rename_all = screaming_snake.then(|| quote! {
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+    }).unwrap_or_default();
+    let enums = variants.iter().map(|(original_name, name)| {
+        let serde_attr = if screaming_snake {
+            TokenStream::new()
+        } else {
+            codegen::serde_rename(original_name, name)
+        };
         quote! {
             #serde_attr
             #name
         }
     });
     let name = e.name.to_rust_struct();
+    // Derived `Ord` follows variant order, so variants must stay in declaration order.
+    let ord = config.ordered_enums.then(|| quote! {
+        , PartialOrd, Ord
+    }).unwrap_or_default();
+    // Variants never carry data, so the enum is always `Copy` and can key maps and sets.
     quote! {
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize #ord)]
+        #rename_all
         pub enum #name {
             #(#enums,)*
         }
     }
 }
 
+/// Whether every value is what serde's `SCREAMING_SNAKE_CASE` makes of its variant, so a single
+/// `rename_all` can replace the per-variant renames.
+fn uses_screaming_snake_variants(variants: &[(String, Ident)]) -> bool {
+    // serde's rule, which splits before each uppercase letter but not before digits.
+    let screaming_snake = |ident: &str| {
+        let mut s = String::new();
+        for (i, c) in ident.char_indices() {
+            if i > 0 && c.is_uppercase() {
+                s.push('_');
+            }
+            s.push(c.to_ascii_uppercase());
+        }
+        s
+    };
+    !variants.is_empty()
+        && variants.iter().all(|(value, ident)| screaming_snake(&ident.0) == *value)
+        && variants.iter().any(|(value, ident)| *value != ident.0)
+}
+
+
+/// A unit struct that always (de)serializes as the same string. Used for enums with a single value.
+fn create_marker_struct(name: &str, value: &str) -> TokenStream {
+    let name = name.to_rust_struct();
+    quote! {
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+        pub struct #name;
+        impl #name {
+            pub const VALUE: &'static str = #value;
+        }
+        impl Serialize for #name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(Self::VALUE)
+            }
+        }
+        impl<'de> Deserialize<'de> for #name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = String::deserialize(deserializer)?;
+                if value == Self::VALUE {
+                    Ok(Self)
+                } else {
+                    Err(serde::de::Error::invalid_value(serde::de::Unexpected::Str(&value), &Self::VALUE))
+                }
+            }
+        }
+    }
+}
+
+/// Whether every struct variant's fields are the camelCase form of their Rust names, so a single
+/// `rename_all_fields` can replace the per-field renames.
+fn uses_camel_case_fields(schema: &Union) -> bool {
+    let mut names = schema.variants.iter().flat_map(|v| v.fields.keys()).peekable();
+    if names.peek().is_none() {
+        return false;
+    }
+    let mut renamed = false;
+    for name in names {
+        let ident = name.to_rust_ident().0;
+        if ident.to_case(Case::Camel) != *name {
+            return false;
+        }
+        renamed |= ident != *name;
+    }
+    renamed
+}
+
+/// Unions are untagged, so serde picks the first variant that deserializes.
+pub fn create_union_enum(schema: &Union, config: &ConfigFlags, spec: &HirSpec) -> TokenStream {
+    let name = schema.name.to_rust_struct();
+    let docs = schema.docs.clone().to_rust_code();
+    let camel_case = uses_camel_case_fields(schema);
+    let variants = schema.variants.iter().map(|v| {
+        let variant = v.name.to_rust_struct();
+        let rename = v.tag_value.as_ref().map(|value| codegen::serde_rename(value, &variant)).unwrap_or_default();
+        if v.is_struct() {
+            let fields = v.fields.iter().map(|(name, field)| {
+                let ident = name.to_rust_ident();
+                // With `rename_all_fields`, the decorators shouldn't rename the field again.
+                let decorators = if camel_case {
+                    field.decorators(&ident.0, config, spec)
+                } else {
+                    field.decorators(name, config, spec)
+                };
+                let mut ty = field.ty.to_rust_type();
+                if field.is_rust_option() {
+                    ty = quote!(Option<#ty>);
+                }
+                quote! {
+                    #(#decorators)*
+                    #ident: #ty
+                }
+            });
+            return quote!(#rename #variant { #(#fields,)* });
+        }
+        let mut ty = v.field.ty.to_rust_type();
+        if v.field.optional {
+            ty = quote!(Option<#ty>);
+        }
+        quote!(#rename #variant(#ty))
+    });
+    let tagging = match (&schema.tag, &schema.content) {
+        (Some(tag), Some(content)) => quote!(#[serde(tag = #tag, content = #content)]),
+        (Some(tag), None) => quote!(#[serde(tag = #tag)]),
+        _ => quote!(#[serde(untagged)]),
+    };
+    let rename_all_fields = camel_case.then(|| quote! {
+        #[serde(rename_all_fields = "camelCase")]
+    }).unwrap_or_default();
+    quote! {
+        #docs
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #tagging
+        #rename_all_fields
+        pub enum #name {
+            #(#variants,)*
+        }
+    }
+}
 
 pub fn create_newtype_struct(schema: &NewType, spec: &HirSpec) -> TokenStream {
     let name = schema.name.to_rust_struct();
@@ -305,9 +650,65 @@ pub fn create_newtype_struct(schema: &NewType, spec: &HirSpec) -> TokenStream {
     let default = schema.fields.iter().all(|f| f.implements_default(spec))
         .then(|| { quote! { , Default } })
         .unwrap_or_default();
+    let validate = schema.not.as_deref().and_then(|not| {
+        let check = match not {
+            "null" => quote!(self.0.is_null()),
+            "string" => quote!(self.0.is_string()),
+            "number" => quote!(self.0.is_number()),
+            "integer" => quote!(self.0.is_i64() || self.0.is_u64()),
+            "boolean" => quote!(self.0.is_boolean()),
+            "array" => quote!(self.0.is_array()),
+            "object" => quote!(self.0.is_object()),
+            _ => return None,
+        };
+        let message = format!("value must not be {}", not);
+        Some(quote! {
+            impl #name {
+                /// Checks the value against the schema's `not` constraint.
+                pub fn validate(&self) -> Result<(), String> {
+                    if #check {
+                        return Err(#message.to_string());
+                    }
+                    Ok(())
+                }
+            }
+        })
+    }).unwrap_or_default();
+    let string_impls = newtype_string_impls(schema, &name);
+    // Spelled out so every serde format, not just JSON, sees the bare inner value.
+    let transparent = (schema.fields.len() == 1).then(|| quote!(#[serde(transparent)]));
     quote! {
         #[derive(Debug, Clone, Serialize, Deserialize #default)]
+        #transparent
         pub struct #name(#(pub #fields),*);
+        #validate
+        #string_impls
+    }
+}
+
+/// `FromStr` and `Display` for newtypes over a string. Validated formats parse through their own `FromStr`.
+fn newtype_string_impls(schema: &NewType, name: &Ident) -> TokenStream {
+    let (err, parse) = match schema.fields.as_slice() {
+        [HirField { ty: Ty::String, .. }] => (quote!(std::convert::Infallible), quote!(Ok(Self(s.to_string())))),
+        [HirField { ty: Ty::Format(format), .. }] if format.is_validated() => {
+            (quote!(crate::validated::FormatError), quote!(s.parse().map(Self)))
+        }
+        _ => return TokenStream::new(),
+    };
+    quote! {
+        impl std::str::FromStr for #name {
+            type Err = #err;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                #parse
+            }
+        }
+
+        impl std::fmt::Display for #name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Display::fmt(&self.0, f)
+            }
+        }
     }
 }
 
@@ -323,17 +724,44 @@ pub fn create_typealias(name: &str, schema: &HirField) -> TokenStream {
 }
 
 pub fn create_struct(record: &Record, config: &ConfigFlags, spec: &HirSpec) -> TokenStream {
-    match record {
+    // Type aliases can't take derives.
+    let jsonschema = (config.jsonschema && !matches!(record, Record::TypeAlias(..))).then(|| quote! {
+        #[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
+    }).unwrap_or_default();
+    let item = match record {
         Record::Struct(s) => create_sumtype_struct(s, config, spec),
         Record::NewType(nt) => create_newtype_struct(nt, spec),
-        Record::Enum(en) => create_enum_struct(en),
+        Record::Enum(en) => create_enum_struct(en, config),
         Record::TypeAlias(name, field) => create_typealias(name, field),
+        Record::Union(u) => create_union_enum(u, config, spec),
+    };
+    // Type aliases can point at foreign types like `Vec<T>`, which can't take the impl.
+    let try_from = (!matches!(record, Record::TypeAlias(..)))
+        .then(|| create_try_from_value(record.name()))
+        .unwrap_or_default();
+    quote! {
+        #jsonschema
+        #item
+        #try_from
+    }
+}
+
+/// Converts dynamic JSON into the model.
+fn create_try_from_value(name: &str) -> TokenStream {
+    let name = name.to_rust_struct();
+    quote! {
+        impl TryFrom<serde_json::Value> for #name {
+            type Error = serde_json::Error;
+            fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+                serde_json::from_value(value)
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use hir::{HirField, Ty};
+    use hir::{HirField, StringFormat, Ty, UnionVariant};
 
     use crate::rust::format::format_code;
 
@@ -349,12 +777,502 @@ mod tests {
                 ..HirField::default()
             }],
             docs: None,
+            not: None,
         };
         let code = create_newtype_struct(&schema, &HirSpec::default());
         let code = format_code(code).unwrap();
         assert_eq!(&code, "
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(transparent)]
 pub struct NewType(pub String);
+impl std::str::FromStr for NewType {
+    type Err = std::convert::Infallible;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
+impl std::fmt::Display for NewType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
 ".trim());
     }
+
+    #[test]
+    fn test_validated_newtype_from_str() {
+        let schema = NewType {
+            name: "Link".to_string(),
+            fields: vec![HirField {
+                ty: Ty::Format(StringFormat::Uri),
+                ..HirField::default()
+            }],
+            docs: None,
+            not: None,
+        };
+        let code = create_newtype_struct(&schema, &HirSpec::default());
+        let code = format_code(code).unwrap();
+        // Serializes as the bare string, the same as the `Uri` it wraps.
+        assert!(code.contains("#[serde(transparent)]\npub struct Link(pub crate::validated::Uri);"));
+        assert!(code.contains("type Err = crate::validated::FormatError;"));
+        assert!(code.contains("s.parse().map(Self)"));
+        assert!(code.contains("impl std::fmt::Display for Link {"));
+    }
+
+    #[test]
+    fn test_comma_separated_field() {
+        let schema = Struct {
+            name: "Filter".to_string(),
+            nullable: false,
+            fields: [("tags".to_string(), HirField {
+                ty: Ty::Array(Box::new(Ty::String)),
+                comma_separated: true,
+                ..HirField::default()
+            })].into_iter().collect(),
+            docs: None,
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("#[serde(with = \"crate::serde::comma_separated\")]"));
+        assert!(code.contains("pub tags: Vec<String>,"));
+    }
+
+    #[test]
+    fn test_ip_address_fields() {
+        let schema = Struct {
+            name: "Server".to_string(),
+            nullable: false,
+            fields: [
+                ("ipv4".to_string(), HirField { ty: Ty::Format(StringFormat::Ipv4), ..HirField::default() }),
+                ("ipv6".to_string(), HirField { ty: Ty::Format(StringFormat::Ipv6), ..HirField::default() }),
+            ].into_iter().collect(),
+            docs: None,
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("pub ipv4: std::net::Ipv4Addr,"));
+        assert!(code.contains("pub ipv6: std::net::Ipv6Addr,"));
+        assert!(code.contains("#[derive(Debug, Clone, Serialize, Deserialize)]"));
+    }
+
+    #[test]
+    fn test_field_aliases() {
+        let field = HirField {
+            ty: Ty::String,
+            aliases: vec!["email_address".to_string(), "mail".to_string()],
+            ..HirField::default()
+        };
+        let schema = Struct {
+            name: "User".to_string(),
+            fields: [("email".to_string(), field)].into_iter().collect(),
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("#[serde(alias = \"email_address\")]"));
+        assert!(code.contains("#[serde(alias = \"mail\")]"));
+        assert!(code.contains("pub email: String,"));
+    }
+
+    #[test]
+    fn test_adjacently_tagged_union() {
+        let schema = Union {
+            name: "Event".to_string(),
+            variants: vec![
+                UnionVariant { name: "Created".to_string(), field: HirField { ty: Ty::model("Created"), ..HirField::default() }, ..UnionVariant::default() },
+                UnionVariant { name: "Deleted".to_string(), field: HirField { ty: Ty::model("Deleted"), ..HirField::default() }, ..UnionVariant::default() },
+            ],
+            tag: Some("type".to_string()),
+            content: Some("data".to_string()),
+            ..Union::default()
+        };
+        let code = create_union_enum(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("#[serde(tag = \"type\", content = \"data\")]"));
+        assert!(!code.contains("untagged"));
+        assert!(code.contains("Created(Created),"));
+    }
+
+    #[test]
+    fn test_discriminator_variant_rename() {
+        let schema = Union {
+            name: "Pet".to_string(),
+            variants: vec![
+                UnionVariant { name: "Cat".to_string(), field: HirField { ty: Ty::model("Cat"), ..HirField::default() }, tag_value: Some("cat".to_string()), ..UnionVariant::default() },
+                UnionVariant { name: "Dog".to_string(), field: HirField { ty: Ty::model("Dog"), ..HirField::default() }, tag_value: Some("Dog".to_string()), ..UnionVariant::default() },
+            ],
+            tag: Some("petType".to_string()),
+            ..Union::default()
+        };
+        let code = format_code(create_union_enum(&schema, &ConfigFlags::default(), &HirSpec::default())).unwrap();
+        assert!(code.contains("#[serde(tag = \"petType\")]"));
+        assert!(code.contains("#[serde(rename = \"cat\")]\n    Cat(Cat),"));
+        assert!(code.contains("    Dog(Dog),"));
+        assert!(!code.contains("rename = \"Dog\""));
+    }
+
+    #[test]
+    fn test_rename_all_fields() {
+        let field = |ty| HirField { ty, ..HirField::default() };
+        let schema = Union {
+            name: "Payment".to_string(),
+            variants: vec![
+                UnionVariant {
+                    name: "Card".to_string(),
+                    fields: [
+                        ("cardNumber".to_string(), field(Ty::String)),
+                        ("expiryMonth".to_string(), field(Ty::String)),
+                    ].into_iter().collect(),
+                    ..UnionVariant::default()
+                },
+                UnionVariant {
+                    name: "Bank".to_string(),
+                    fields: [("accountNumber".to_string(), field(Ty::String))].into_iter().collect(),
+                    ..UnionVariant::default()
+                },
+            ],
+            ..Union::default()
+        };
+        let code = create_union_enum(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("#[serde(rename_all_fields = \"camelCase\")]"));
+        assert!(code.contains("    Card {\n        card_number: String,\n        expiry_month: String,\n    },"));
+        assert!(!code.contains("rename = "));
+    }
+
+    #[test]
+    fn test_internal_field_skipped() {
+        let schema = Struct {
+            name: "User".to_string(),
+            fields: [
+                ("name".to_string(), HirField { ty: Ty::String, ..HirField::default() }),
+                ("debug_info".to_string(), HirField { ty: Ty::String, optional: true, internal: true, ..HirField::default() }),
+            ].into_iter().collect(),
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("#[serde(skip)]\n    pub debug_info: Option<String>,"));
+        assert!(!code.contains("skip_serializing_if = \"Option::is_none\""));
+
+        let mut spec = HirSpec {
+            schemas: [("User".to_string(), Record::Struct(schema))].into_iter().collect(),
+            ..HirSpec::default()
+        };
+        spec.remove_internal_fields();
+        let code = format_code(create_struct(spec.get_record("User").unwrap(), &ConfigFlags::default(), &spec)).unwrap();
+        assert!(!code.contains("debug_info"));
+    }
+
+    #[test]
+    fn test_unix_timestamp_field() {
+        let unix_seconds = Ty::DateTime { serialization: DateTimeSerialization::UnixSeconds };
+        let schema = Struct {
+            name: "Event".to_string(),
+            fields: [
+                ("created".to_string(), HirField { ty: unix_seconds.clone(), ..HirField::default() }),
+                ("deleted".to_string(), HirField { ty: unix_seconds, optional: true, ..HirField::default() }),
+            ].into_iter().collect(),
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("#[serde(with = \"chrono::serde::ts_seconds\")]\n    pub created: chrono::DateTime<chrono::Utc>,"));
+        assert!(code.contains("#[serde(with = \"chrono::serde::ts_seconds_option\")]"));
+    }
+
+    #[test]
+    fn test_char_field() {
+        let schema = Struct {
+            name: "Grade".to_string(),
+            fields: [("letter".to_string(), HirField { ty: Ty::Char, ..HirField::default() })].into_iter().collect(),
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        // serde already (de)serializes `char` as a one-character string.
+        assert!(code.contains("pub letter: char,"));
+    }
+
+    #[test]
+    fn test_unsigned_field() {
+        let schema = Struct {
+            name: "Page".to_string(),
+            fields: [
+                ("count".to_string(), HirField { ty: Ty::Unsigned { bits: 64 }, ..HirField::default() }),
+                ("size".to_string(), HirField { ty: Ty::Unsigned { bits: 32 }, ..HirField::default() }),
+            ].into_iter().collect(),
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("pub count: u64,"));
+        assert!(code.contains("pub size: u32,"));
+    }
+
+    #[test]
+    fn test_new_skips_read_only_fields() {
+        let schema = Struct {
+            name: "Pet".to_string(),
+            fields: [
+                ("id".to_string(), HirField { ty: Ty::String, read_only: true, ..HirField::default() }),
+                ("name".to_string(), HirField { ty: Ty::String, ..HirField::default() }),
+                ("tag".to_string(), HirField { ty: Ty::String, optional: true, ..HirField::default() }),
+            ].into_iter().collect(),
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("pub fn new(name: String) -> Self {"));
+        assert!(code.contains("id: Default::default(),"));
+        assert!(code.contains("tag: None,"));
+    }
+
+    #[test]
+    fn test_read_write_only_skips() {
+        let schema = Struct {
+            name: "Account".to_string(),
+            fields: [
+                ("id".to_string(), HirField { ty: Ty::String, read_only: true, ..HirField::default() }),
+                ("password".to_string(), HirField { ty: Ty::String, write_only: true, ..HirField::default() }),
+                ("name".to_string(), HirField { ty: Ty::String, ..HirField::default() }),
+            ].into_iter().collect(),
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("#[serde(skip_serializing)]\n    pub id: String,"));
+        assert!(code.contains("#[serde(skip_deserializing)]\n    pub password: String,"));
+        assert!(code.contains("    pub name: String,"));
+        assert_eq!(code.matches("#[serde(skip_").count(), 2);
+    }
+
+    #[test]
+    fn test_write_only_without_default() {
+        let schema = Struct {
+            name: "Device".to_string(),
+            fields: [
+                ("ip".to_string(), HirField { ty: Ty::Format(StringFormat::Ipv4), write_only: true, ..HirField::default() }),
+                ("gateway".to_string(), HirField { ty: Ty::Format(StringFormat::Ipv4), write_only: true, optional: true, ..HirField::default() }),
+            ].into_iter().collect(),
+            ..Struct::default()
+        };
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        // `Ipv4Addr` has no `Default`, so a required field can't be skipped.
+        assert!(code.contains("    pub ip: std::net::Ipv4Addr,"));
+        assert!(!code.contains("#[serde(skip_deserializing)]\n    pub ip"));
+        // The optional one defaults to `None`.
+        assert_eq!(code.matches("#[serde(skip_deserializing)]").count(), 1);
+    }
+
+    #[test]
+    fn test_list_response_collects() {
+        let schema = Struct {
+            name: "PetList".to_string(),
+            fields: [
+                ("data".to_string(), HirField { ty: Ty::Array(Box::new(Ty::model("Pet"))), ..HirField::default() }),
+                ("total".to_string(), HirField { ty: Ty::integer(), ..HirField::default() }),
+                ("next_cursor".to_string(), HirField { ty: Ty::String, optional: true, ..HirField::default() }),
+            ].into_iter().collect(),
+            ..Struct::default()
+        };
+        let spec = HirSpec {
+            operations: vec![hir::Operation { name: "listPets".to_string(), ret: Ty::model("PetList"), ..hir::Operation::default() }],
+            ..HirSpec::default()
+        };
+        let code = format_code(create_sumtype_struct(&schema, &ConfigFlags::default(), &spec)).unwrap();
+        assert!(code.contains("impl Extend<Pet> for PetList {"));
+        assert!(code.contains("impl FromIterator<Pet> for PetList {"));
+        assert!(code.contains("data: iter.into_iter().collect(),"));
+        assert!(code.contains("total: Default::default(),"));
+        assert!(code.contains("next_cursor: None,"));
+
+        // Not returned by any operation, so not a list response.
+        let code = format_code(create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default())).unwrap();
+        assert!(!code.contains("FromIterator"));
+    }
+
+    #[test]
+    fn test_model_file_layout() {
+        let spec = HirSpec {
+            schemas: [
+                ("Pet".to_string(), Record::Struct(Struct { name: "Pet".to_string(), ..Struct::default() })),
+                ("Owner".to_string(), Record::Struct(Struct { name: "Owner".to_string(), ..Struct::default() })),
+            ].into_iter().collect(),
+            ..HirSpec::default()
+        };
+        let code = generate_model_rs(&spec, &ConfigFlags::default()).to_rust_code();
+        let code = format_code(code).unwrap();
+        assert!(code.contains("pub use pet::*;"));
+        assert!(code.contains("mod owner;"));
+        assert!(!code.contains("pub struct Pet"));
+
+        let code = generate_combined_model_rs(&spec, &ConfigFlags::default()).to_rust_code();
+        let code = format_code(code).unwrap();
+        assert!(code.contains("pub struct Pet {"));
+        assert!(code.contains("pub struct Owner {"));
+        assert!(!code.contains("mod pet;"));
+    }
+
+    #[test]
+    fn test_jsonschema_derive() {
+        let record = Record::Enum(StrEnum {
+            name: "Priority".to_string(),
+            variants: vec!["low".to_string(), "high".to_string()],
+            docs: None,
+        });
+        let config = ConfigFlags {
+            jsonschema: true,
+            ..ConfigFlags::default()
+        };
+        let code = create_struct(&record, &config, &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.starts_with("#[cfg_attr(feature = \"jsonschema\", derive(schemars::JsonSchema))]\n#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]\npub enum Priority {"));
+
+        let alias = Record::TypeAlias("Ids".to_string(), HirField { ty: Ty::Array(Box::new(Ty::String)), ..HirField::default() });
+        let code = format_code(create_struct(&alias, &config, &HirSpec::default())).unwrap();
+        assert!(!code.contains("jsonschema"));
+    }
+
+    #[test]
+    fn test_try_from_value() {
+        let record = Record::Enum(StrEnum {
+            name: "Priority".to_string(),
+            variants: vec!["low".to_string(), "high".to_string()],
+            docs: None,
+        });
+        let code = format_code(create_struct(&record, &ConfigFlags::default(), &HirSpec::default())).unwrap();
+        // Lets callers write `Priority::try_from(json!("low"))`.
+        assert!(code.contains("impl TryFrom<serde_json::Value> for Priority {"));
+        assert!(code.contains("serde_json::from_value(value)"));
+
+        let alias = Record::TypeAlias("Ids".to_string(), HirField { ty: Ty::Array(Box::new(Ty::String)), ..HirField::default() });
+        let code = format_code(create_struct(&alias, &ConfigFlags::default(), &HirSpec::default())).unwrap();
+        assert!(!code.contains("TryFrom"));
+    }
+
+    #[test]
+    fn test_keyword_enum_variants() {
+        let schema = StrEnum {
+            name: "Scope".to_string(),
+            variants: vec!["self".to_string(), "type".to_string(), "crate".to_string(), "Self".to_string()],
+            docs: None,
+        };
+        let code = format_code(create_enum_struct(&schema, &ConfigFlags::default())).unwrap();
+        assert!(code.contains("#[serde(rename = \"self\")]\n    SelfStruct,"));
+        assert!(code.contains("#[serde(rename = \"type\")]\n    Type,"));
+        assert!(code.contains("#[serde(rename = \"crate\")]\n    Crate,"));
+        // `Self` collides with `self`, so it takes the next free name.
+        assert!(code.contains("#[serde(rename = \"Self\")]\n    SelfStruct2,"));
+    }
+
+    #[test]
+    fn test_screaming_snake_enum() {
+        let schema = StrEnum {
+            name: "Status".to_string(),
+            variants: vec!["IN_PROGRESS".to_string(), "DONE".to_string(), "NOT_STARTED".to_string()],
+            docs: None,
+        };
+        let code = format_code(create_enum_struct(&schema, &ConfigFlags::default())).unwrap();
+        assert!(code.contains("#[serde(rename_all = \"SCREAMING_SNAKE_CASE\")]\npub enum Status {"));
+        assert!(code.contains("    InProgress,\n    Done,\n    NotStarted,\n"));
+        assert!(!code.contains("rename = "));
+
+        // One odd value out keeps the per-variant renames.
+        let schema = StrEnum {
+            variants: vec!["IN_PROGRESS".to_string(), "done".to_string()],
+            ..schema
+        };
+        let code = format_code(create_enum_struct(&schema, &ConfigFlags::default())).unwrap();
+        assert!(!code.contains("rename_all"));
+        assert!(code.contains("#[serde(rename = \"IN_PROGRESS\")]"));
+    }
+
+    #[test]
+    fn test_enum_map_key() {
+        let schema = StrEnum {
+            name: "Color".to_string(),
+            variants: vec!["red".to_string(), "green".to_string()],
+            docs: None,
+        };
+        let code = format_code(create_enum_struct(&schema, &ConfigFlags::default())).unwrap();
+        // `HashMap<Color, _>` needs `Eq + Hash`; `Copy` lets keys be passed around by value.
+        assert!(code.contains("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]"));
+        assert!(code.contains("pub enum Color {"));
+    }
+
+    #[test]
+    fn test_ordered_enums() {
+        let schema = StrEnum {
+            name: "Priority".to_string(),
+            variants: vec!["low".to_string(), "high".to_string(), "critical".to_string()],
+            docs: None,
+        };
+        let config = ConfigFlags {
+            ordered_enums: true,
+            ..ConfigFlags::default()
+        };
+        let code = create_enum_struct(&schema, &config);
+        let code = format_code(code).unwrap();
+        assert!(code.contains("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, PartialOrd, Ord)]"));
+        let low = code.find("    Low,").unwrap();
+        let high = code.find("    High,").unwrap();
+        let critical = code.find("    Critical,").unwrap();
+        assert!(low < high && high < critical);
+
+        let code = format_code(create_enum_struct(&schema, &ConfigFlags::default())).unwrap();
+        assert!(!code.contains("Ord"));
+    }
+
+    #[test]
+    fn test_single_variant_marker() {
+        let schema = StrEnum {
+            name: "ObjectType".to_string(),
+            variants: vec!["account".to_string()],
+            docs: None,
+        };
+        let config = ConfigFlags {
+            single_variant_marker: true,
+            ..ConfigFlags::default()
+        };
+        let code = create_enum_struct(&schema, &config);
+        let code = format_code(code).unwrap();
+        assert!(code.contains("pub struct ObjectType;"));
+        assert!(code.contains("pub const VALUE: &'static str = \"account\";"));
+        assert!(!code.contains("pub enum"));
+
+        let code = create_enum_struct(&schema, &ConfigFlags::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("pub enum ObjectType"));
+    }
+
+    #[test]
+    fn test_dependent_required_validate() {
+        let optional = HirField { ty: Ty::String, optional: true, ..HirField::default() };
+        let schema = Struct {
+            name: "Payment".to_string(),
+            fields: [
+                ("card_number".to_string(), optional.clone()),
+                ("billing_address".to_string(), optional),
+            ].into_iter().collect(),
+            dependent_required: [("card_number".to_string(), vec!["billing_address".to_string()])].into_iter().collect(),
+            ..Struct::default()
+        };
+        let config = ConfigFlags {
+            validate: true,
+            ..ConfigFlags::default()
+        };
+        let code = create_sumtype_struct(&schema, &config, &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(code.contains("pub fn validate(&self) -> Result<(), crate::ValidationError> {"));
+        assert!(code.contains("if self.card_number.is_some() && self.billing_address.is_none() {"));
+        assert!(code.contains("field: \"billing_address\","));
+
+        let code = create_sumtype_struct(&schema, &ConfigFlags::default(), &HirSpec::default());
+        let code = format_code(code).unwrap();
+        assert!(!code.contains("pub fn validate"));
+    }
 }
\ No newline at end of file
@@ -7,18 +7,20 @@ use quote::{quote, ToTokens};
 use regex::Captures;
 
 use hir::{Doc, HirSpec, Operation};
-use hir::{doc, Location, Parameter, Ty, Language};
+use hir::{doc, ArrayStyle, Location, Parameter, Ty, Language};
 use ln_core::extractor::spec_defines_auth;
-use ln_core::PackageConfig;
+use ln_core::{ConfigFlags, PackageConfig};
 use mir::{Class, Field, FnArg, Function, Ident, Visibility};
 
-use crate::rust::codegen::ToRustCode;
+use crate::rust::codegen::{method_name, serde_rename, ToRustCode};
 use crate::rust::codegen::ToRustIdent;
 use crate::rust::codegen::ToRustType;
 
 pub fn assign_inputs_to_request(inputs: &[Parameter]) -> TokenStream {
     let params_except_path: Vec<&Parameter> = inputs.iter().filter(|&input| input.location != Location::Path).collect();
-    if params_except_path.iter().all(|&input| input.location == Location::Query) {
+    if params_except_path.iter().all(|&input| {
+        input.location == Location::Query && !input.json_content && input.default.is_none() && !input.ty.is_iterable()
+    }) {
         return quote! {
             r = r.set_query(self.params);
         };
@@ -30,9 +32,11 @@ pub fn assign_inputs_to_request(inputs: &[Parameter]) -> TokenStream {
             let name = &input.name;
             let field = name.to_rust_ident();
 
+            // JSON content is serialized whole, so arrays aren't split into repeated keys.
+            let repeated = input.ty.is_iterable() && input.location != Location::Body && !input.json_content;
             let mut assign = {
                 let param_key = input.to_key().to_rust_code();
-                let value_identifier = if input.ty.is_iterable() && input.location != Location::Body
+                let value_identifier = if repeated
                 {
                     quote! { item }
                 } else if input.optional {
@@ -40,24 +44,40 @@ pub fn assign_inputs_to_request(inputs: &[Parameter]) -> TokenStream {
                 } else {
                     quote! { self.params.#field }
                 };
+                let value = if input.json_content {
+                    quote! { serde_json::to_string(&#value_identifier).unwrap() }
+                } else {
+                    quote! { #value_identifier.to_string() }
+                };
                 match input.location {
                     Location::Path => panic!("Should be filtered."),
                     Location::Body => quote! {
                         r = r.json(json!({#param_key: #value_identifier}));
                     },
                     Location::Query => quote! {
-                        r = r.query(#param_key, &#value_identifier.to_string());
+                        r = r.query(#param_key, &#value);
                     },
                     Location::Header => quote! {
-                        r = r.header(#param_key, &#value_identifier.to_string());
+                        r = r.header(#param_key, &#value);
                     },
                     Location::Cookie => quote! {
-                        r = r.cookie(#param_key, &#value_identifier.to_string());
+                        r = r.cookie(#param_key, &#value);
                     },
                 }
             };
 
-            if input.ty.is_iterable() && input.location != Location::Body {
+            if repeated && input.location == Location::Query {
+                let container = if input.optional {
+                    quote! { unwrapped }
+                } else {
+                    quote! { &self.params.#field }
+                };
+                let style = array_style(input);
+                let name = name.as_str();
+                assign = quote! {
+                    r = #style.apply(r, #name, #container);
+                };
+            } else if repeated {
                 let container = if input.optional {
                     quote! { unwrapped }
                 } else {
@@ -71,10 +91,15 @@ pub fn assign_inputs_to_request(inputs: &[Parameter]) -> TokenStream {
             }
 
             if input.optional {
+                let fallback = default_assign(input, repeated).map(|fallback| quote! {
+                    else {
+                        #fallback
+                    }
+                });
                 assign = quote! {
                     if let Some(ref unwrapped) = self.params.#field {
                         #assign
-                    }
+                    } #fallback
                 };
             }
             assign
@@ -84,6 +109,205 @@ pub fn assign_inputs_to_request(inputs: &[Parameter]) -> TokenStream {
     }
 }
 
+/// The `QueryArrayStyle` for an array query parameter: the one the spec fixes, or else the client's.
+fn array_style(input: &Parameter) -> TokenStream {
+    match input.array_style {
+        Some(ArrayStyle::Repeat) => quote!(crate::QueryArrayStyle::Repeat),
+        Some(ArrayStyle::Brackets) => quote!(crate::QueryArrayStyle::Brackets),
+        Some(ArrayStyle::Comma) => quote!(crate::QueryArrayStyle::Comma),
+        None => quote!(self.client.query_array_style),
+    }
+}
+
+/// Sends the parameter's `default` when the caller leaves it unset. Body fields are left to the server.
+fn default_assign(input: &Parameter, repeated: bool) -> Option<TokenStream> {
+    let default = input.default.as_ref()?;
+    if let (serde_json::Value::Array(items), true, Location::Query) = (default, repeated, input.location) {
+        if items.is_empty() {
+            return None;
+        }
+        let style = array_style(input);
+        let name = input.name.as_str();
+        let values = items.iter().map(default_string);
+        return Some(quote! {
+            r = #style.apply(r, #name, [#(#values),*]);
+        });
+    }
+    let values: Vec<String> = match default {
+        serde_json::Value::Array(items) if repeated => items.iter().map(default_string).collect(),
+        _ if input.json_content => vec![default.to_string()],
+        _ => vec![default_string(default)],
+    };
+    let param_key = input.to_key().to_rust_code();
+    let method = match input.location {
+        Location::Query => quote!(query),
+        Location::Header => quote!(header),
+        Location::Cookie => quote!(cookie),
+        Location::Path | Location::Body => return None,
+    };
+    Some(quote! {
+        #(r = r.#method(#param_key, #values);)*
+    })
+}
+
+/// Strings are sent as-is, rather than as quoted JSON.
+fn default_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// What the request resolves to. HEAD and OPTIONS requests give back the response itself, for its
+/// status and headers, since there's no body to parse.
+pub fn response_type(operation: &Operation) -> TokenStream {
+    if operation.headers_only() {
+        quote!(httpclient::InMemoryResponse)
+    } else {
+        operation.ret.to_rust_type()
+    }
+}
+
+/// Builds the request for an operation and implements `IntoFuture` to send it. With `request_builders`,
+/// the built-but-unsent request is also public, for callers who need to adjust it before sending.
+pub fn impl_send_request(operation: &Operation, spec: &HirSpec, opt: &PackageConfig) -> TokenStream {
+    let struct_name = operation.request_struct_name().to_rust_struct();
+    let response = response_type(operation);
+    let method = syn::Ident::new(&operation.method, proc_macro2::Span::call_site());
+    let url = build_url(operation);
+    let assign_inputs = assign_inputs_to_request(&operation.parameters);
+    let accept = set_accept_header(operation);
+    // `json` marks the body `application/json`, so this comes after the inputs to replace it.
+    let content_type = operation.merge_patch.then(|| quote! {
+        r = r.header("Content-Type", "application/merge-patch+json");
+    }).unwrap_or_default();
+    let authenticate = spec.has_security().then(|| quote! {
+        r = self.client.authenticate(r);
+    }).unwrap_or_default();
+    let gzip = opt.config.gzip_requests
+        .then(crate::rust::compression::gzip_request_body)
+        .unwrap_or_default();
+    let operation_name = operation.name.as_str();
+    let parse = match &operation.envelope {
+        _ if operation.headers_only() => quote!(Ok(res)),
+        Some(field) => quote! {
+            #[derive(serde::Deserialize)]
+            struct Envelope<T> {
+                #[serde(rename = #field)]
+                value: T,
+            }
+            res.json::<Envelope<#response>>()
+                .map(|envelope| envelope.value)
+                .map_err(|e| crate::ClientError::from_httpclient(#operation_name, e))
+        },
+        None => quote! {
+            res.json()
+                .map_err(|e| crate::ClientError::from_httpclient(#operation_name, e))
+        },
+    };
+    let check_scopes = (opt.config.check_scopes && !operation.scopes.is_empty()).then(|| {
+        let required = &operation.scopes;
+        quote! {
+            if let Some(scopes) = &self.client.scopes {
+                let missing: Vec<String> = [#(#required),*].iter()
+                    .filter(|required| !scopes.iter().any(|s| s == *required))
+                    .map(|required| required.to_string())
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(crate::ClientError::MissingScopes { operation: #operation_name, missing });
+                }
+            }
+        }
+    }).unwrap_or_default();
+    let cancellable = opt.config.cancellation.then(|| quote! {
+        #[cfg(feature = "cancellation")]
+        impl<'a> FluentRequest<'a, #struct_name> {
+            /// Sends the request, giving up with `ClientError::Cancelled` if `token` is cancelled first.
+            pub async fn cancellable(self, token: &tokio_util::sync::CancellationToken) -> Result<#response, crate::ClientError> {
+                let request = ::std::future::IntoFuture::into_future(self);
+                let cancelled = ::std::pin::pin!(token.cancelled());
+                match ::futures::future::select(request, cancelled).await {
+                    ::futures::future::Either::Left((res, _)) => res,
+                    ::futures::future::Either::Right(_) => Err(crate::ClientError::Cancelled { operation: #operation_name }),
+                }
+            }
+        }
+    }).unwrap_or_default();
+    // HEAD and OPTIONS already return the raw response.
+    let raw = (opt.config.raw_responses && !operation.headers_only()).then(|| quote! {
+        impl<'a> FluentRequest<'a, #struct_name> {
+            /// Sends the request and returns the response without parsing it. Read the body
+            /// with `res.body()` to borrow it rather than decode it.
+            pub async fn raw(self) -> Result<httpclient::InMemoryResponse, crate::ClientError> {
+                #check_scopes
+                let r = self.into_request_builder();
+                r.await.map_err(|e| crate::ClientError::from_httpclient(#operation_name, e))
+            }
+        }
+    }).unwrap_or_default();
+    let vis = if opt.config.request_builders {
+        quote!(pub)
+    } else {
+        quote!()
+    };
+    quote! {
+        impl<'a> FluentRequest<'a, #struct_name> {
+            #vis fn into_request_builder(self) -> httpclient::RequestBuilder<'a> {
+                let url = #url;
+                let mut r = self.client.client.#method(url);
+                #assign_inputs
+                #content_type
+                #accept
+                let agent = self.client.user_agent.as_deref().unwrap_or(crate::USER_AGENT);
+                r = r.header("User-Agent", agent);
+                #authenticate
+                #gzip
+                if let Some(signer) = &self.client.signer {
+                    r = signer.sign(r);
+                }
+                r
+            }
+        }
+
+        impl<'a> ::std::future::IntoFuture for FluentRequest<'a, #struct_name> {
+            type Output = Result<#response, crate::ClientError>;
+            type IntoFuture = ::futures::future::BoxFuture<'a, Self::Output>;
+
+            fn into_future(self) -> Self::IntoFuture {
+                Box::pin(async move {
+                    #check_scopes
+                    let r = self.into_request_builder();
+                    let res = r.await
+                        .map_err(|e| crate::ClientError::from_httpclient(#operation_name, e))?;
+                    #parse
+                })
+            }
+        }
+
+        #cancellable
+        #raw
+    }
+}
+
+/// Sets the `Accept` header, preferring the client's override. Operations that document several
+/// response media types list them in the order the spec gives.
+pub fn set_accept_header(operation: &Operation) -> TokenStream {
+    let default = (operation.response_media_types.len() > 1).then(|| {
+        let accept = operation.response_media_types.join(", ");
+        quote! {
+            else {
+                r = r.header("Accept", #accept);
+            }
+        }
+    }).unwrap_or_default();
+    quote! {
+        if let Some(accept) = &self.client.accept {
+            r = r.header("Accept", accept.as_str());
+        }
+        #default
+    }
+}
+
 /// This is complicated because we need to interpolate any param values.
 pub fn build_url(operation: &Operation) -> TokenStream {
     let inputs = operation
@@ -91,8 +315,10 @@ pub fn build_url(operation: &Operation) -> TokenStream {
         .iter()
         .filter(|a| a.location == Location::Path)
         .collect::<Vec<_>>();
+    // An absolute url bypasses the client's base url.
+    let server = operation.server.as_deref().unwrap_or_default().trim_end_matches('/');
     if inputs.is_empty() {
-        let path = &operation.path;
+        let path = format!("{}{}", server, operation.path);
         quote! {
             #path
         }
@@ -110,6 +336,7 @@ pub fn build_url(operation: &Operation) -> TokenStream {
                 format!("{{{}}}", cap.get(1).unwrap().as_str().to_case(Case::Snake))
             })
             .to_string();
+        let path = format!("{}{}", server.replace('{', "{{").replace('}', "}}"), path);
         quote! {
             &format!(#path, #(#inputs),*)
         }
@@ -186,50 +413,117 @@ pub fn build_struct_fields(
         .collect()
 }
 
+/// Serde field renames, for structs that derive `Serialize`. Keywords get a trailing underscore
+/// (`type_`), and `petId` becomes `pet_id`, so this keeps the original name on the wire.
+fn with_serde_renames(mut fields: Vec<Field<TokenStream>>) -> Vec<Field<TokenStream>> {
+    for field in &mut fields {
+        let rename = serde_rename(&field.name, &field.name.to_rust_ident());
+        if !rename.is_empty() {
+            field.decorators.push(rename);
+        }
+    }
+    fields
+}
+
 /// Build the various "builder" methods for optional parameters for a request struct
 pub fn build_request_struct_builder_methods(
     operation: &Operation,
+    config: &ConfigFlags,
 ) -> Vec<Function<TokenStream>> {
-    operation.parameters.iter().filter(|a| a.optional).map(|a| {
-        let name = a.name.to_rust_ident();
-        let mut arg_type = a.ty.to_reference_type(TokenStream::new());
-
-        let mut body = if a.ty.is_reference_type() {
-            quote! {
-                self.params.#name = Some(#name.to_owned());
-                self
-            }
-        } else {
-            quote! {
-                self.params.#name = Some(#name);
-                self
-            }
-        };
-        if let Some(Ty::String) = a.ty.inner_iterable() {
-            arg_type = quote!( impl IntoIterator<Item = impl AsRef<str>> );
-            body = quote! {
-                self.params.#name = Some(#name.into_iter().map(|s| s.as_ref().to_owned()).collect());
-                self
-            };
+    let in_params_struct = params_struct_params(operation, config);
+    operation.parameters.iter()
+        .filter(|a| a.optional && !in_params_struct.iter().any(|p| p.name == a.name))
+        .map(|a| build_setter(a, quote!(self.params)))
+        .collect()
+}
+
+/// A builder method that sets the optional parameter's field on `target`.
+fn build_setter(a: &Parameter, target: TokenStream) -> Function<TokenStream> {
+    let name = a.name.to_rust_ident();
+    let mut arg_type = a.ty.to_reference_type(TokenStream::new());
+
+    let mut body = if a.ty.is_reference_type() {
+        quote! {
+            #target.#name = Some(#name.to_owned());
+            self
         }
-        let name: Ident = a.name.to_rust_ident();
-        Function {
-            doc: doc(format!("Set the value of the {} field.", name.0)),
-            name,
-            args: vec![
-                FnArg {
-                    name: a.name.to_rust_ident().into(),
-                    ty: arg_type,
-                    default: None,
-                    treatment: None,
-                }
-            ],
-            ret: quote! {Self},
-            body,
-            public: true,
-            ..Function::default()
+    } else {
+        quote! {
+            #target.#name = Some(#name);
+            self
         }
-    }).collect()
+    };
+    if let Some(Ty::String) = a.ty.inner_iterable() {
+        arg_type = quote!( impl IntoIterator<Item = impl AsRef<str>> );
+        body = quote! {
+            #target.#name = Some(#name.into_iter().map(|s| s.as_ref().to_owned()).collect());
+            self
+        };
+    }
+    // Serializing to a `Value` only fails for maps with non-string keys.
+    if let Ty::Any = a.ty {
+        arg_type = quote!(impl serde::Serialize);
+        body = quote! {
+            #target.#name = Some(serde_json::to_value(#name).expect("value must serialize to JSON"));
+            self
+        };
+    }
+    let name: Ident = a.name.to_rust_ident();
+    Function {
+        doc: doc(format!("Set the value of the {} field.", name.0)),
+        name,
+        args: vec![
+            FnArg {
+                name: a.name.to_rust_ident().into(),
+                ty: arg_type,
+                default: None,
+                treatment: None,
+            }
+        ],
+        ret: quote! {Self},
+        body,
+        public: true,
+        ..Function::default()
+    }
+}
+
+/// With `query_params_structs`, operations with more optional query parameters than this take them as a struct.
+const CROWDED_QUERY_PARAMS: usize = 3;
+
+/// The optional query parameters that go in the operation's `{Operation}Params` struct. Empty if it doesn't have one.
+pub fn params_struct_params<'a>(operation: &'a Operation, config: &ConfigFlags) -> Vec<&'a Parameter> {
+    if !config.query_params_structs {
+        return Vec::new();
+    }
+    let params: Vec<&Parameter> = operation.parameters.iter()
+        .filter(|p| p.optional && p.location == Location::Query)
+        .collect();
+    if params.len() > CROWDED_QUERY_PARAMS {
+        params
+    } else {
+        Vec::new()
+    }
+}
+
+/// The `{Operation}Params` builder for the operation's optional query parameters, e.g.
+/// `ListUsersParams::default().page(2).limit(50)`.
+fn build_params_struct(operation: &Operation, opt: &PackageConfig) -> Option<Class<TokenStream>> {
+    let params = params_struct_params(operation, &opt.config);
+    if params.is_empty() {
+        return None;
+    }
+    let fn_name = method_name(operation, &opt.config).0;
+    let client = opt.client_name().to_rust_struct().to_string().replace(" ", "");
+    let fields = params.iter().map(|&p| p.clone()).collect::<Vec<_>>();
+    Some(Class {
+        name: operation.params_struct_name().to_rust_struct(),
+        doc: Some(Doc(format!("Optional query parameters for [`{client}::{fn_name}`]."))),
+        instance_fields: with_serde_renames(build_struct_fields(&fields, false)),
+        mut_self_instance_methods: params.iter().map(|p| build_setter(p, quote!(self))).collect(),
+        public: true,
+        decorators: vec![quote! {#[derive(Debug, Clone, Default, Serialize, Deserialize)]}],
+        ..Class::default()
+    })
 }
 
 pub fn build_request_struct(
@@ -237,7 +531,7 @@ pub fn build_request_struct(
     spec: &HirSpec,
     opt: &PackageConfig,
 ) -> Vec<Class<TokenStream>> {
-    let mut instance_fields = build_struct_fields(&operation.parameters, false);
+    let mut instance_fields = with_serde_renames(build_struct_fields(&operation.parameters, false));
     // instance_fields.insert(
     //     0,
     //     Field {
@@ -254,8 +548,8 @@ pub fn build_request_struct(
     // let mut instance_methods = vec![build_send_function(operation, spec)];
     // let mut_self_instance_methods = build_request_struct_builder_methods(operation);
 
-    let fn_name = operation.name.to_rust_ident().0;
-    let response = operation.ret.to_rust_type().to_string().replace(" ", "");
+    let fn_name = method_name(operation, &opt.config).0;
+    let response = response_type(operation).to_string().replace(" ", "");
     let client = opt.client_name().to_rust_struct().to_string().replace(" ", "");
     let doc = Some(Doc(format!(r#"You should use this struct via [`{client}::{fn_name}`].
 
@@ -300,6 +594,7 @@ On request success, this will return a [`{response}`]."#, )));
             ..Class::default()
         });
     }
+    result.extend(build_params_struct(operation, opt));
 
     result
 }
@@ -325,3 +620,385 @@ pub fn generate_request_model_rs(spec: &HirSpec, opt: &PackageConfig) -> TokenSt
         #(#request_structs)*
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ln_core::ConfigFlags;
+
+    use crate::rust::codegen::codegen_function;
+    use crate::rust::format::format_code;
+    use crate::rust::test_util::{param, petstore_opt};
+
+    use super::*;
+
+    #[test]
+    fn test_accept_header() {
+        let operation = Operation {
+            name: "getReport".to_string(),
+            response_media_types: vec!["application/json".to_string(), "text/csv".to_string()],
+            ..Operation::default()
+        };
+        let code = set_accept_header(&operation);
+        let code = format_code(quote!(fn f() { #code })).unwrap();
+        assert!(code.contains("r = r.header(\"Accept\", \"application/json, text/csv\");"));
+
+        let operation = Operation {
+            response_media_types: vec!["application/json".to_string()],
+            ..operation
+        };
+        let code = set_accept_header(&operation);
+        let code = format_code(quote!(fn f() { #code })).unwrap();
+        assert!(!code.contains("application/json"));
+        assert!(code.contains("r = r.header(\"Accept\", accept.as_str());"));
+    }
+
+    #[test]
+    fn test_json_content_query_param() {
+        let params = vec![Parameter {
+            json_content: true,
+            ..param("filter", Ty::model("Filter"), Location::Query)
+        }];
+        let code = assign_inputs_to_request(&params);
+        let code = format_code(quote!(fn f() { #code })).unwrap();
+        assert!(!code.contains("set_query"));
+        assert!(code.contains("r = r.query(\"filter\", &serde_json::to_string(&self.params.filter).unwrap());"));
+    }
+
+    #[test]
+    fn test_query_array_style() {
+        let mut params = vec![param("ids", Ty::Array(Box::new(Ty::integer())), Location::Query)];
+        let code = assign_inputs_to_request(&params);
+        let code = format_code(quote!(fn f() { #code })).unwrap();
+        assert!(!code.contains("set_query"));
+        assert!(code.contains("r = self.client.query_array_style.apply(r, \"ids\", &self.params.ids);"));
+
+        // `explode: false` in the spec overrides the client's style.
+        params[0].array_style = Some(ArrayStyle::Comma);
+        let code = assign_inputs_to_request(&params);
+        let code = format_code(quote!(fn f() { #code })).unwrap();
+        assert!(code.contains("r = crate::QueryArrayStyle::Comma.apply(r, \"ids\", &self.params.ids);"));
+
+        // `ids[]=1&ids[]=2`, `ids=1&ids=2` and `ids=1,2` respectively.
+        let styles = format_code(crate::rust::client::query_array_style_enum()).unwrap();
+        assert!(styles.contains("let key = format!(\"{}[]\", key);"));
+        assert!(styles.contains("r = r.query(key, &value.to_string());"));
+        assert!(styles.contains(".join(\",\");"));
+    }
+
+    #[test]
+    fn test_patch_double_option() {
+        let params = vec![Parameter {
+            optional: true,
+            ..param("nickname", Ty::Option(Box::new(Ty::String)), Location::Body)
+        }];
+        let fields = build_struct_fields(&params, false);
+        assert_eq!(fields[0].ty.to_string(), quote!(Option<Option<String>>).to_string());
+        let setter = codegen_function(build_setter(&params[0], quote!(self.params)), quote!(mut self,));
+        let setter = format_code(quote!(impl X { #setter })).unwrap();
+        assert!(setter.contains("pub fn nickname(mut self, nickname: Option<String>) -> Self {"));
+        assert!(setter.contains("self.params.nickname = Some(nickname);"));
+        // Unset sends nothing; `Some(None)` sends `"nickname": null`.
+        let code = assign_inputs_to_request(&params);
+        let code = format_code(quote!(fn f() { #code })).unwrap();
+        assert!(code.contains("if let Some(ref unwrapped) = self.params.nickname {"));
+        assert!(code.contains("r = r.json(json!("));
+    }
+
+    #[test]
+    fn test_param_default_when_omitted() {
+        let params = vec![Parameter {
+            optional: true,
+            default: Some(serde_json::json!(20)),
+            ..param("limit", Ty::integer(), Location::Query)
+        }];
+        let code = assign_inputs_to_request(&params);
+        let code = format_code(quote!(fn f() { #code })).unwrap();
+        assert!(!code.contains("set_query"));
+        assert!(code.contains("if let Some(ref unwrapped) = self.params.limit {"));
+        assert!(code.contains("r = r.query(\"limit\", &unwrapped.to_string());"));
+        assert!(code.contains("} else {"));
+        assert!(code.contains("r = r.query(\"limit\", \"20\");"));
+    }
+
+    #[test]
+    fn test_any_setter_accepts_serialize() {
+        let operation = Operation {
+            name: "createEvent".to_string(),
+            parameters: vec![Parameter {
+                optional: true,
+                ..param("metadata", Ty::Any, Location::Body)
+            }],
+            ..Operation::default()
+        };
+        let methods = build_request_struct_builder_methods(&operation, &ConfigFlags::default());
+        let arg = &methods[0].args[0].ty;
+        assert_eq!(arg.to_string(), "impl serde :: Serialize");
+        let body = &methods[0].body;
+        let code = format_code(quote!(fn f() { #body })).unwrap();
+        assert!(code.contains("self.params.metadata = Some(serde_json::to_value(metadata).expect(\"value must serialize to JSON\"));"));
+    }
+
+    #[test]
+    fn test_request_builder_escape_hatch() {
+        let operation = Operation {
+            name: "getPet".to_string(),
+            method: "get".to_string(),
+            path: "/pets/{id}".to_string(),
+            parameters: vec![param("id", Ty::String, Location::Path)],
+            ..Operation::default()
+        };
+        let mut opt = petstore_opt(ConfigFlags::default());
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(code.contains("    fn into_request_builder(self) -> httpclient::RequestBuilder<'a> {"));
+        assert!(code.contains("let r = self.into_request_builder();"));
+
+        opt.config.request_builders = true;
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(code.contains("pub fn into_request_builder(self) -> httpclient::RequestBuilder<'a> {"));
+        assert!(code.contains("format!(\"/pets/{id}\", id = self.params.id)"));
+    }
+
+    #[test]
+    fn test_required_scopes_check() {
+        let operation = Operation {
+            name: "deletePets".to_string(),
+            method: "delete".to_string(),
+            path: "/pets".to_string(),
+            scopes: vec!["pets:write".to_string()],
+            ..Operation::default()
+        };
+        let mut opt = petstore_opt(ConfigFlags::default());
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(!code.contains("MissingScopes"));
+
+        opt.config.check_scopes = true;
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        // A token without `pets:write` is rejected before the request is built.
+        let check = code.find("if let Some(scopes) = &self.client.scopes {").unwrap();
+        assert!(check < code.find("let r = self.into_request_builder();").unwrap());
+        assert!(code.contains("[\"pets:write\"]"));
+        assert!(code.contains("return Err(crate::ClientError::MissingScopes {"));
+    }
+
+    #[test]
+    fn test_cancellable_request() {
+        let operation = Operation {
+            name: "exportPets".to_string(),
+            method: "get".to_string(),
+            path: "/pets/export".to_string(),
+            ret: Ty::Any,
+            ..Operation::default()
+        };
+        let mut opt = petstore_opt(ConfigFlags::default());
+        opt.config.cancellation = true;
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(code.contains("#[cfg(feature = \"cancellation\")]\nimpl<'a> FluentRequest<'a, ExportPetsRequest> {"));
+        assert!(code.contains("pub async fn cancellable("));
+        // Whichever finishes first wins, so a cancelled token drops the in-flight request.
+        assert!(code.contains("match ::futures::future::select(request, cancelled).await {"));
+        assert!(code.contains("Err(crate::ClientError::Cancelled { operation: \"exportPets\" })"));
+    }
+
+    #[test]
+    fn test_request_awaits_directly() {
+        let operation = Operation {
+            name: "listPets".to_string(),
+            method: "get".to_string(),
+            path: "/pets".to_string(),
+            parameters: vec![Parameter {
+                optional: true,
+                ..param("limit", Ty::integer(), Location::Query)
+            }],
+            ret: Ty::Array(Box::new(Ty::model("Pet"))),
+            ..Operation::default()
+        };
+        let opt = petstore_opt(ConfigFlags::default());
+        // Awaiting the request object sends it: nothing is built until `into_future`.
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(code.contains("impl<'a> ::std::future::IntoFuture for FluentRequest<'a, ListPetsRequest> {"));
+        assert!(code.contains("type Output = Result<Vec<Pet>, crate::ClientError>;"));
+        assert!(code.contains("fn into_future(self) -> Self::IntoFuture {"));
+
+        // Optional params are set on the request object before awaiting it.
+        let setter = build_request_struct_builder_methods(&operation, &ConfigFlags::default()).remove(0);
+        let setter = codegen_function(setter, quote!(mut self,));
+        let setter = format_code(quote!(impl X { #setter })).unwrap();
+        assert!(setter.contains("pub fn limit(mut self, limit: i64) -> Self {"));
+        assert!(setter.contains("self.params.limit = Some(limit);"));
+    }
+
+    #[test]
+    fn test_query_params_struct() {
+        let query = |name: &str| Parameter {
+            optional: true,
+            ..param(name, Ty::integer(), Location::Query)
+        };
+        let operation = Operation {
+            name: "listUsers".to_string(),
+            method: "get".to_string(),
+            path: "/users".to_string(),
+            parameters: vec![query("page"), query("limit"), query("since"), query("until")],
+            ..Operation::default()
+        };
+        let mut opt = petstore_opt(ConfigFlags::default());
+        assert_eq!(build_request_struct(&operation, &HirSpec::default(), &opt).len(), 1);
+
+        opt.config.query_params_structs = true;
+        let structs = build_request_struct(&operation, &HirSpec::default(), &opt);
+        let params = structs.into_iter().last().unwrap().to_rust_code();
+        let params = format_code(params).unwrap();
+        assert!(params.contains("#[derive(Debug, Clone, Default, Serialize, Deserialize)]\npub struct ListUsersParams {"));
+        assert!(params.contains("pub fn page(mut self, page: i64) -> Self {"));
+        assert!(params.contains("self.page = Some(page);"));
+        // The params are moved onto the request, which no longer has its own setters for them.
+        assert!(build_request_struct_builder_methods(&operation, &opt.config).is_empty());
+        let method = format_code(crate::rust::client::build_api_client_method(&operation, &opt.config)).unwrap();
+        assert!(method.contains("params: request::ListUsersParams"));
+        assert!(method.contains("page: params.page,"));
+        // So they're still serialized together as the query string.
+        let assign = assign_inputs_to_request(&operation.parameters);
+        let code = format_code(quote!(fn f() { #assign })).unwrap();
+        assert!(code.contains("r = r.set_query(self.params);"));
+    }
+
+    #[test]
+    fn test_head_returns_response() {
+        let operation = Operation {
+            name: "petExists".to_string(),
+            method: "head".to_string(),
+            path: "/pets".to_string(),
+            ..Operation::default()
+        };
+        let opt = petstore_opt(ConfigFlags::default());
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(code.contains("let mut r = self.client.client.head(url);"));
+        assert!(code.contains("type Output = Result<httpclient::InMemoryResponse, crate::ClientError>;"));
+        // The status and headers are all there is, so the body is never parsed.
+        assert!(!code.contains("res.json()"));
+        assert!(code.contains("Ok(res)"));
+    }
+
+    #[test]
+    fn test_operation_server() {
+        let operation = Operation {
+            name: "uploadFile".to_string(),
+            path: "/files/{id}".to_string(),
+            parameters: vec![param("id", Ty::String, Location::Path)],
+            server: Some("https://uploads.example.com/".to_string()),
+            ..Operation::default()
+        };
+        let code = build_url(&operation).to_string();
+        assert_eq!(code, quote!(&format!("https://uploads.example.com/files/{id}", id = self.params.id)).to_string());
+
+        let operation = Operation {
+            path: "/pets".to_string(),
+            server: None,
+            ..Operation::default()
+        };
+        assert_eq!(build_url(&operation).to_string(), quote!("/pets").to_string());
+    }
+
+    #[test]
+    fn test_request_signer() {
+        let operation = Operation {
+            name: "createPet".to_string(),
+            method: "post".to_string(),
+            path: "/pets".to_string(),
+            ..Operation::default()
+        };
+        let opt = petstore_opt(ConfigFlags { gzip_requests: true, ..ConfigFlags::default() });
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        let gzip = code.find("crate::compression::gzip_request_body").unwrap();
+        let sign = code.find("r = signer.sign(r);").unwrap();
+        assert!(gzip < sign, "the signature must cover the final body");
+        assert!(code.contains("if let Some(signer) = &self.client.signer {"));
+
+        let code = format_code(crate::rust::client::request_signer_trait()).unwrap();
+        assert!(code.contains("fn sign<'a>(&self, request: httpclient::RequestBuilder<'a>) -> httpclient::RequestBuilder<'a>;"));
+    }
+
+    #[test]
+    fn test_merge_patch_content_type() {
+        let mut operation = Operation {
+            name: "updatePet".to_string(),
+            method: "patch".to_string(),
+            path: "/pets".to_string(),
+            parameters: vec![Parameter {
+                optional: true,
+                ..param("nickname", Ty::Option(Box::new(Ty::String)), Location::Body)
+            }],
+            merge_patch: true,
+            ..Operation::default()
+        };
+        let opt = petstore_opt(ConfigFlags::default());
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        let body = code.find("r = r.json(json!(").unwrap();
+        let header = code.find("r = r.header(\"Content-Type\", \"application/merge-patch+json\");").unwrap();
+        assert!(body < header, "the merge patch type must replace the one `json` sets");
+
+        operation.merge_patch = false;
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(!code.contains("merge-patch"));
+    }
+
+    #[test]
+    fn test_primitive_response() {
+        let operation = Operation {
+            name: "getToken".to_string(),
+            method: "get".to_string(),
+            path: "/token".to_string(),
+            ret: Ty::String,
+            ..Operation::default()
+        };
+        let opt = petstore_opt(ConfigFlags::default());
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(code.contains("type Output = Result<String, crate::ClientError>;"));
+        assert!(code.contains("res.json()"));
+    }
+
+    #[test]
+    fn test_raw_response() {
+        let operation = Operation {
+            name: "getPet".to_string(),
+            method: "get".to_string(),
+            path: "/pets".to_string(),
+            ret: Ty::model("Pet"),
+            ..Operation::default()
+        };
+        let mut opt = petstore_opt(ConfigFlags::default());
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(!code.contains("pub async fn raw("));
+
+        opt.config.raw_responses = true;
+        let code = format_code(impl_send_request(&operation, &HirSpec::default(), &opt)).unwrap();
+        assert!(code.contains("pub async fn raw(self)"));
+        assert!(code.contains("Result<httpclient::InMemoryResponse, crate::ClientError>"));
+        // The body is handed back as received, never decoded into a `Pet`.
+        let raw = &code[code.find("pub async fn raw(").unwrap()..];
+        assert!(!raw.contains("res.json()"));
+        assert!(raw.contains("crate::ClientError::from_httpclient(\"getPet\", e)"));
+    }
+
+    #[test]
+    fn test_required_struct_field_renames() {
+        let path = |name: &str| param(name, Ty::String, Location::Path);
+        let operation = Operation {
+            name: "getPetToy".to_string(),
+            method: "get".to_string(),
+            path: "/owners/{ownerId}/pets/{petId}/toys/{toyId}/{part}".to_string(),
+            parameters: vec![path("ownerId"), path("petId"), path("toyId"), path("part")],
+            ..Operation::default()
+        };
+        let opt = petstore_opt(ConfigFlags::default());
+        assert!(operation.use_required_struct(Language::Rust));
+        let structs = build_request_struct(&operation, &HirSpec::default(), &opt);
+        let [request, required] = structs.try_into().ok().unwrap();
+        let request = format_code(request.to_rust_code()).unwrap();
+        assert!(request.contains("#[serde(rename = \"petId\")]\n    pub pet_id: String,"));
+        // The required struct doesn't derive serde, so a `serde` attribute wouldn't compile there.
+        let required = format_code(required.to_rust_code()).unwrap();
+        assert!(required.contains("pub struct GetPetToyRequired<'a> {"));
+        assert!(required.contains("pub pet_id: &'a str,"));
+        assert!(!required.contains("serde"));
+    }
+}
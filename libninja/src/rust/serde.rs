@@ -152,4 +152,70 @@ pub fn option_chrono_naive_date_as_int_module() -> TokenStream {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+pub fn comma_separated_module() -> TokenStream {
+    quote! {
+        pub mod comma_separated {
+            use std::fmt::Display;
+            use std::str::FromStr;
+            use serde::{Deserialize, Deserializer, Serializer};
+            use serde::de::Error;
+
+            pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+                where
+                    T: FromStr,
+                    T::Err: Display,
+                    D: Deserializer<'de>,
+            {
+                let value = String::deserialize(deserializer)?;
+                if value.is_empty() {
+                    return Ok(Vec::new());
+                }
+                value.split(',').map(|s| s.parse().map_err(Error::custom)).collect()
+            }
+
+            pub fn serialize<T, S>(value: &[T], serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    T: Display,
+                    S: Serializer,
+            {
+                let value = value.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+                serializer.serialize_str(&value)
+            }
+        }
+
+        pub mod option_comma_separated {
+            use std::fmt::Display;
+            use std::str::FromStr;
+            use serde::{Deserialize, Deserializer, Serializer};
+            use serde::de::Error;
+
+            pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+                where
+                    T: FromStr,
+                    T::Err: Display,
+                    D: Deserializer<'de>,
+            {
+                let Some(value) = Option::<String>::deserialize(deserializer)? else {
+                    return Ok(None);
+                };
+                if value.is_empty() {
+                    return Ok(Some(Vec::new()));
+                }
+                value.split(',').map(|s| s.parse().map_err(Error::custom)).collect::<Result<_, _>>().map(Some)
+            }
+
+            pub fn serialize<T, S>(value: &Option<Vec<T>>, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    T: Display,
+                    S: Serializer,
+            {
+                match value {
+                    Some(value) => super::comma_separated::serialize(value, serializer),
+                    None => serializer.serialize_none(),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use hir::{Language, Location, Parameter, Ty};
+use ln_core::{ConfigFlags, PackageConfig};
+
+pub(crate) fn petstore_opt(config: ConfigFlags) -> PackageConfig {
+    PackageConfig {
+        package_name: "petstore".to_string(),
+        service_name: "Petstore".to_string(),
+        language: Language::Rust,
+        package_version: "0.1.0".to_string(),
+        config,
+        dest: Default::default(),
+    }
+}
+
+/// A required parameter, with everything else unset.
+pub(crate) fn param(name: &str, ty: Ty, location: Location) -> Parameter {
+    Parameter {
+        name: name.to_string(),
+        ty,
+        location,
+        optional: false,
+        doc: None,
+        example: None,
+        json_content: false,
+        default: None,
+        array_style: None,
+    }
+}
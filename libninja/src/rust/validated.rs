@@ -0,0 +1,585 @@
+use std::collections::BTreeSet;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+
+use hir::{PropertyCount, StringFormat};
+use mir::Ident;
+
+use crate::rust::codegen::ToRustIdent;
+
+/// Name of the generated newtype for a validated format, e.g. `Hostname`.
+pub fn validated_type_name(format: StringFormat) -> Ident {
+    format.as_str().to_rust_struct()
+}
+
+/// Name of the generated newtype for a `multipleOf` constraint, e.g. `MultipleOf5`.
+pub fn multiple_of_type_name(multiple: u64) -> Ident {
+    Ident(format!("MultipleOf{}", multiple))
+}
+
+/// Name of the generated newtype for a `minProperties`/`maxProperties` constraint, e.g. `MapMin1Max3`.
+pub fn bounded_map_type_name(count: PropertyCount) -> Ident {
+    let min = count.min.map(|min| format!("Min{}", min)).unwrap_or_default();
+    let max = count.max.map(|max| format!("Max{}", max)).unwrap_or_default();
+    Ident(format!("Map{}{}", min, max))
+}
+
+/// Generate the validated.rs file, which has newtypes for values that are checked on construction:
+/// string formats, integers with `multipleOf`, and maps with a bounded number of properties.
+pub fn generate_validated_rs(formats: &BTreeSet<StringFormat>, multiples: &BTreeSet<u64>, bounded_maps: &BTreeSet<PropertyCount>) -> TokenStream {
+    let newtypes = formats.iter()
+        .filter(|f| f.is_validated())
+        .map(|f| validated_newtype(*f));
+    let multiple_of = (!multiples.is_empty()).then(|| {
+        let newtypes = multiples.iter().map(|m| multiple_of_newtype(*m));
+        quote! {
+            /// Returned when an integer isn't a multiple of what the type requires.
+            #[derive(Debug, Clone, PartialEq, Eq)]
+            pub struct MultipleOfError {
+                pub multiple: i64,
+                pub value: i64,
+            }
+
+            impl fmt::Display for MultipleOfError {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{} is not a multiple of {}", self.value, self.multiple)
+                }
+            }
+
+            impl std::error::Error for MultipleOfError {}
+
+            #(#newtypes)*
+        }
+    }).unwrap_or_default();
+    let bounded_map = (!bounded_maps.is_empty()).then(|| {
+        let newtypes = bounded_maps.iter().map(|c| bounded_map_newtype(*c));
+        quote! {
+            /// Returned when a map has too few or too many properties for the type.
+            #[derive(Debug, Clone, PartialEq, Eq)]
+            pub struct PropertyCountError {
+                pub min: usize,
+                pub max: usize,
+                pub len: usize,
+            }
+
+            impl fmt::Display for PropertyCountError {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{} properties, expected between {} and {}", self.len, self.min, self.max)
+                }
+            }
+
+            impl std::error::Error for PropertyCountError {}
+
+            #(#newtypes)*
+        }
+    }).unwrap_or_default();
+    quote! {
+        use std::fmt;
+        use std::str::FromStr;
+        use serde::{Serialize, Deserialize};
+
+        /// Returned when a string doesn't match the format of the type it's parsed into.
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct FormatError {
+            pub format: &'static str,
+            pub value: String,
+        }
+
+        impl fmt::Display for FormatError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{:?} is not a valid {}", self.value, self.format)
+            }
+        }
+
+        impl std::error::Error for FormatError {}
+
+        #(#newtypes)*
+        #multiple_of
+        #bounded_map
+    }
+}
+
+fn bounded_map_newtype(count: PropertyCount) -> TokenStream {
+    let name = bounded_map_type_name(count);
+    let min = proc_macro2::Literal::usize_unsuffixed(count.min.unwrap_or_default() as usize);
+    let max = count.max
+        .map(|max| proc_macro2::Literal::usize_unsuffixed(max as usize).into_token_stream())
+        .unwrap_or_else(|| quote!(usize::MAX));
+    quote! {
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        #[serde(try_from = "serde_json::Map<String, serde_json::Value>", into = "serde_json::Map<String, serde_json::Value>")]
+        pub struct #name(serde_json::Map<String, serde_json::Value>);
+
+        impl #name {
+            pub const MIN_PROPERTIES: usize = #min;
+            pub const MAX_PROPERTIES: usize = #max;
+
+            pub fn new(map: serde_json::Map<String, serde_json::Value>) -> Result<Self, PropertyCountError> {
+                Self::check(map.len())?;
+                Ok(Self(map))
+            }
+
+            fn check(len: usize) -> Result<(), PropertyCountError> {
+                if (Self::MIN_PROPERTIES..=Self::MAX_PROPERTIES).contains(&len) {
+                    Ok(())
+                } else {
+                    Err(PropertyCountError { min: Self::MIN_PROPERTIES, max: Self::MAX_PROPERTIES, len })
+                }
+            }
+
+            /// Fails if a new key would take the map over `MAX_PROPERTIES`.
+            pub fn insert(&mut self, key: String, value: serde_json::Value) -> Result<Option<serde_json::Value>, PropertyCountError> {
+                if !self.0.contains_key(&key) {
+                    Self::check(self.0.len() + 1)?;
+                }
+                Ok(self.0.insert(key, value))
+            }
+
+            /// Fails if removing the key would take the map under `MIN_PROPERTIES`.
+            pub fn remove(&mut self, key: &str) -> Result<Option<serde_json::Value>, PropertyCountError> {
+                if self.0.contains_key(key) {
+                    Self::check(self.0.len() - 1)?;
+                }
+                Ok(self.0.remove(key))
+            }
+
+            pub fn get(&self, key: &str) -> Option<&serde_json::Value> {
+                self.0.get(key)
+            }
+
+            pub fn as_map(&self) -> &serde_json::Map<String, serde_json::Value> {
+                &self.0
+            }
+
+            pub fn iter(&self) -> serde_json::map::Iter<'_> {
+                self.0.iter()
+            }
+        }
+
+        impl IntoIterator for #name {
+            type Item = (String, serde_json::Value);
+            type IntoIter = serde_json::map::IntoIter;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.into_iter()
+            }
+        }
+
+        impl<'a> IntoIterator for &'a #name {
+            type Item = (&'a String, &'a serde_json::Value);
+            type IntoIter = serde_json::map::Iter<'a>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.iter()
+            }
+        }
+
+        impl TryFrom<serde_json::Map<String, serde_json::Value>> for #name {
+            type Error = PropertyCountError;
+
+            fn try_from(map: serde_json::Map<String, serde_json::Value>) -> Result<Self, Self::Error> {
+                Self::new(map)
+            }
+        }
+
+        impl From<#name> for serde_json::Map<String, serde_json::Value> {
+            fn from(value: #name) -> Self {
+                value.0
+            }
+        }
+    }
+}
+
+fn multiple_of_newtype(multiple: u64) -> TokenStream {
+    let name = multiple_of_type_name(multiple);
+    let multiple = proc_macro2::Literal::i64_unsuffixed(multiple as i64);
+    quote! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+        #[serde(try_from = "i64", into = "i64")]
+        pub struct #name(i64);
+
+        impl #name {
+            pub const MULTIPLE: i64 = #multiple;
+
+            pub fn new(value: i64) -> Result<Self, MultipleOfError> {
+                if value % Self::MULTIPLE == 0 {
+                    Ok(Self(value))
+                } else {
+                    Err(MultipleOfError { multiple: Self::MULTIPLE, value })
+                }
+            }
+
+            pub fn get(&self) -> i64 {
+                self.0
+            }
+        }
+
+        impl TryFrom<i64> for #name {
+            type Error = MultipleOfError;
+
+            fn try_from(value: i64) -> Result<Self, Self::Error> {
+                Self::new(value)
+            }
+        }
+
+        impl From<#name> for i64 {
+            fn from(value: #name) -> Self {
+                value.0
+            }
+        }
+
+        impl fmt::Display for #name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    }
+}
+
+fn validated_newtype(format: StringFormat) -> TokenStream {
+    if format == StringFormat::Regex {
+        return regex_newtype();
+    }
+    let name = validated_type_name(format);
+    let format_name = format.as_str();
+    let check = format_check(format);
+    quote! {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct #name(String);
+
+        impl #name {
+            pub fn new(value: impl Into<String>) -> Result<Self, FormatError> {
+                let value = value.into();
+                let valid = {
+                    let value = value.as_str();
+                    #check
+                };
+                if valid {
+                    Ok(Self(value))
+                } else {
+                    Err(FormatError { format: #format_name, value })
+                }
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl TryFrom<String> for #name {
+            type Error = FormatError;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                Self::new(value)
+            }
+        }
+
+        impl From<#name> for String {
+            fn from(value: #name) -> Self {
+                value.0
+            }
+        }
+
+        impl FromStr for #name {
+            type Err = FormatError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::new(s)
+            }
+        }
+
+        impl fmt::Display for #name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl AsRef<str> for #name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+    }
+}
+
+/// Keeps the pattern as written, so it serializes back unchanged, next to the compiled regex.
+fn regex_newtype() -> TokenStream {
+    let name = validated_type_name(StringFormat::Regex);
+    quote! {
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct #name {
+            pattern: String,
+            compiled: regex::Regex,
+        }
+
+        impl #name {
+            pub fn new(value: impl Into<String>) -> Result<Self, FormatError> {
+                let pattern = value.into();
+                match regex::Regex::new(&pattern) {
+                    Ok(compiled) => Ok(Self { pattern, compiled }),
+                    Err(_) => Err(FormatError { format: "regex", value: pattern }),
+                }
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.pattern
+            }
+
+            pub fn regex(&self) -> &regex::Regex {
+                &self.compiled
+            }
+        }
+
+        impl PartialEq for #name {
+            fn eq(&self, other: &Self) -> bool {
+                self.pattern == other.pattern
+            }
+        }
+
+        impl Eq for #name {}
+
+        impl std::hash::Hash for #name {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.pattern.hash(state)
+            }
+        }
+
+        impl TryFrom<String> for #name {
+            type Error = FormatError;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                Self::new(value)
+            }
+        }
+
+        impl From<#name> for String {
+            fn from(value: #name) -> Self {
+                value.pattern
+            }
+        }
+
+        impl FromStr for #name {
+            type Err = FormatError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::new(s)
+            }
+        }
+
+        impl fmt::Display for #name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.pattern)
+            }
+        }
+
+        impl AsRef<str> for #name {
+            fn as_ref(&self) -> &str {
+                &self.pattern
+            }
+        }
+    }
+}
+
+/// An expression that checks `value: &str` against the format.
+fn format_check(format: StringFormat) -> TokenStream {
+    match format {
+        StringFormat::Ipv4 | StringFormat::Ipv6 | StringFormat::Password | StringFormat::Regex => unreachable!("{:?} is not a validated format", format),
+        StringFormat::Hostname => quote! {
+            value.len() <= 253 && value.split('.').all(|label| {
+                !label.is_empty()
+                    && label.len() <= 63
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            })
+        },
+        // Deliberately loose: quoted local parts may hold `@` and spaces are rare enough to reject,
+        // but anything else RFC 5321 allows gets through.
+        StringFormat::Email => quote! {
+            !value.contains(char::is_whitespace) && value.rsplit_once('@').map_or(false, |(local, domain)| {
+                !local.is_empty()
+                    && !domain.is_empty()
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+                    && !domain.contains("..")
+            })
+        },
+        StringFormat::Uuid => quote! {
+            value.len() == 36 && value.char_indices().all(|(i, c)| match i {
+                8 | 13 | 18 | 23 => c == '-',
+                _ => c.is_ascii_hexdigit(),
+            })
+        },
+        StringFormat::Uri => quote! {
+            value.split_once(':').map_or(false, |(scheme, _)| {
+                scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            }) && !value.contains(char::is_whitespace)
+        },
+        // RFC 6901: empty, or `/`-prefixed reference tokens where `~` only escapes `0` or `1`.
+        StringFormat::JsonPointer => quote! {
+            (value.is_empty() || value.starts_with('/'))
+                && value.split('~').skip(1).all(|rest| rest.starts_with(['0', '1']))
+        },
+        // A non-negative integer without leading zeros, then `#` or a json pointer.
+        StringFormat::RelativeJsonPointer => quote! {
+            {
+                let digits = value.len() - value.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                let (prefix, pointer) = value.split_at(digits);
+                !prefix.is_empty()
+                    && (prefix == "0" || !prefix.starts_with('0'))
+                    && (pointer == "#"
+                        || ((pointer.is_empty() || pointer.starts_with('/'))
+                            && pointer.split('~').skip(1).all(|rest| rest.starts_with(['0', '1']))))
+            }
+        },
+        StringFormat::Color => quote! {
+            value.strip_prefix('#').map_or(false, |hex| {
+                matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+            })
+        },
+        // Crockford base32 leaves out I, L, O and U. A leading digit above 7 would overflow 128 bits.
+        StringFormat::Ulid => quote! {
+            value.len() == 26
+                && value.starts_with(|c: char| ('0'..='7').contains(&c))
+                && value.chars().all(|c| c.is_ascii_alphanumeric() && !matches!(c.to_ascii_uppercase(), 'I' | 'L' | 'O' | 'U'))
+        },
+        StringFormat::Cuid => quote! {
+            value.len() == 25
+                && value.starts_with('c')
+                && value.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rust::format::format_code;
+
+    use super::*;
+
+    #[test]
+    fn test_hostname_newtype() {
+        let formats = BTreeSet::from([StringFormat::Hostname, StringFormat::Ipv4]);
+        let code = format_code(generate_validated_rs(&formats, &BTreeSet::new(), &BTreeSet::new())).unwrap();
+        assert!(code.contains("pub struct Hostname(String);"));
+        assert!(code.contains("impl FromStr for Hostname {"));
+        assert!(!code.contains("Ipv4"));
+        assert!(!code.contains("MultipleOfError"));
+    }
+
+    #[test]
+    fn test_extras_cover_responses_and_webhooks() {
+        let spec = hir::HirSpec {
+            operations: vec![hir::Operation {
+                name: "getHost".to_string(),
+                ret: hir::Ty::Format(StringFormat::Hostname),
+                ..hir::Operation::default()
+            }],
+            webhooks: vec![hir::Webhook {
+                name: "ArchivedWebhook".to_string(),
+                doc: None,
+                ty: hir::Ty::Option(Box::new(hir::Ty::MultipleOf(5))),
+            }],
+            ..hir::HirSpec::default()
+        };
+        let extras = crate::rust::calculate_extras(&spec);
+        assert!(extras.validated_formats.contains(&StringFormat::Hostname));
+        assert!(extras.multiples.contains(&5));
+    }
+
+    #[test]
+    fn test_json_pointer_newtypes() {
+        let formats = BTreeSet::from([StringFormat::JsonPointer, StringFormat::RelativeJsonPointer]);
+        let code = format_code(generate_validated_rs(&formats, &BTreeSet::new(), &BTreeSet::new())).unwrap();
+        assert!(code.contains("pub struct JsonPointer(String);"));
+        assert!(code.contains("impl FromStr for RelativeJsonPointer {"));
+        // Malformed pointers: no leading `/`, or a `~` that doesn't escape `0` or `1`.
+        assert!(code.contains("(value.is_empty() || value.starts_with('/'))"));
+        assert!(code.contains(".all(|rest| rest.starts_with(['0', '1']))"));
+        // Malformed relative pointers: no integer prefix, or one with leading zeros.
+        assert!(code.contains("!prefix.is_empty()"));
+        assert!(code.contains("(prefix == \"0\" || !prefix.starts_with('0'))"));
+        assert!(code.contains("Err(FormatError { format: \"relative-json-pointer\", value })"));
+    }
+
+    #[test]
+    fn test_regex_newtype() {
+        let formats = BTreeSet::from([StringFormat::Regex]);
+        let code = format_code(generate_validated_rs(&formats, &BTreeSet::new(), &BTreeSet::new())).unwrap();
+        assert!(code.contains("pub struct Regex {"));
+        assert!(code.contains("compiled: regex::Regex,"));
+        // An invalid pattern fails to compile and is rejected.
+        assert!(code.contains("match regex::Regex::new(&pattern) {"));
+        assert!(code.contains("Err(_) => Err(FormatError { format: \"regex\", value: pattern }),"));
+        // Only the pattern is serialized.
+        assert!(code.contains("value.pattern"));
+    }
+
+    #[test]
+    fn test_color_newtype() {
+        let formats = BTreeSet::from([StringFormat::Color]);
+        let code = format_code(generate_validated_rs(&formats, &BTreeSet::new(), &BTreeSet::new())).unwrap();
+        assert!(code.contains("pub struct Color(String);"));
+        assert!(code.contains("impl FromStr for Color {"));
+        // Malformed colors: no `#`, a length other than 3 or 6, or a non-hex digit.
+        assert!(code.contains("value.strip_prefix('#')"));
+        assert!(code.contains("matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit())"));
+        assert!(code.contains("Err(FormatError { format: \"color\", value })"));
+    }
+
+    #[test]
+    fn test_id_newtypes() {
+        let formats = BTreeSet::from([StringFormat::Ulid, StringFormat::Cuid]);
+        let code = format_code(generate_validated_rs(&formats, &BTreeSet::new(), &BTreeSet::new())).unwrap();
+        assert!(code.contains("pub struct Ulid(String);"));
+        assert!(code.contains("impl FromStr for Ulid {"));
+        assert!(code.contains("impl fmt::Display for Cuid {"));
+        // Malformed ULIDs: the wrong length, a leading digit above 7, or letters outside Crockford base32.
+        assert!(code.contains("value.len() == 26"));
+        assert!(code.contains("('0'..='7').contains(&c)"));
+        assert!(code.contains("'I' | 'L' | 'O' | 'U'"));
+        assert!(code.contains("Err(FormatError { format: \"ulid\", value })"));
+    }
+
+    #[test]
+    fn test_email_newtype() {
+        let formats = BTreeSet::from([StringFormat::Email]);
+        let code = format_code(generate_validated_rs(&formats, &BTreeSet::new(), &BTreeSet::new())).unwrap();
+        assert!(code.contains("pub struct Email(String);"));
+        // `notanemail` has no `@`, so there's no local part and domain to split it into.
+        assert!(code.contains("value.rsplit_once('@')"));
+        assert!(code.contains(".map_or(false,"));
+        assert!(code.contains("!domain.starts_with('.')"));
+        assert!(code.contains("Err(FormatError { format: \"email\", value })"));
+    }
+
+    #[test]
+    fn test_multiple_of_newtype() {
+        let code = format_code(generate_validated_rs(&BTreeSet::new(), &BTreeSet::from([5]), &BTreeSet::new())).unwrap();
+        assert!(code.contains("pub struct MultipleOf5(i64);"));
+        assert!(code.contains("pub const MULTIPLE: i64 = 5;"));
+        assert!(code.contains("if value % Self::MULTIPLE == 0 {"));
+    }
+
+    #[test]
+    fn test_bounded_map_newtype() {
+        let count = PropertyCount { min: None, max: Some(3) };
+        let code = format_code(generate_validated_rs(&BTreeSet::new(), &BTreeSet::new(), &BTreeSet::from([count]))).unwrap();
+        assert!(code.contains("pub struct MapMax3(serde_json::Map<String, serde_json::Value>);"));
+        assert!(code.contains("pub const MIN_PROPERTIES: usize = 0;"));
+        assert!(code.contains("pub const MAX_PROPERTIES: usize = 3;"));
+        // A map over `maxProperties` is rejected, both on construction and on insert.
+        assert!(code.contains("if (Self::MIN_PROPERTIES..=Self::MAX_PROPERTIES).contains(&len) {"));
+        assert!(code.contains("Self::check(self.0.len() + 1)?;"));
+        assert_eq!(bounded_map_type_name(PropertyCount { min: Some(1), max: None }).0, "MapMin1");
+        // `for (key, value) in &map` works without going through `as_map()`.
+        assert!(code.contains("impl IntoIterator for MapMax3 {"));
+        assert!(code.contains("type Item = (String, serde_json::Value);"));
+        assert!(code.contains("impl<'a> IntoIterator for &'a MapMax3 {"));
+        assert!(code.contains("pub fn iter(&self) -> serde_json::map::Iter<'_> {"));
+    }
+}
@@ -0,0 +1,59 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use hir::HirSpec;
+
+use crate::rust::codegen::{ToRustCode, ToRustIdent, ToRustType};
+
+/// Generate the webhook.rs file, which dispatches inbound webhook payloads to their models.
+pub fn generate_webhook_rs(spec: &HirSpec) -> TokenStream {
+    let variants = spec.webhooks.iter().map(|webhook| {
+        let doc = webhook.doc.clone().to_rust_code();
+        let variant = webhook.name.to_rust_struct();
+        let ty = webhook.ty.to_rust_type();
+        quote! {
+            #doc
+            #variant(#ty)
+        }
+    });
+    let parse_arms = spec.webhooks.iter().map(|webhook| {
+        let name = &webhook.name;
+        let variant = webhook.name.to_rust_struct();
+        quote! {
+            #name => serde_json::from_str(payload).map(Self::#variant)
+        }
+    });
+    let name_arms = spec.webhooks.iter().map(|webhook| {
+        let name = &webhook.name;
+        let variant = webhook.name.to_rust_struct();
+        quote! {
+            Self::#variant(_) => #name
+        }
+    });
+    quote! {
+        use crate::model::*;
+
+        /// An inbound webhook event, tagged by the name of the webhook.
+        #[derive(Debug)]
+        pub enum WebhookEvent {
+            #(#variants,)*
+        }
+
+        impl WebhookEvent {
+            /// Parse the JSON payload of the webhook called `name`.
+            pub fn from_json(name: &str, payload: &str) -> serde_json::Result<Self> {
+                match name {
+                    #(#parse_arms,)*
+                    _ => Err(serde::de::Error::custom(format!("Unknown webhook: {}", name))),
+                }
+            }
+
+            /// The name of the webhook, as it appears in the spec.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(#name_arms,)*
+                }
+            }
+        }
+    }
+}
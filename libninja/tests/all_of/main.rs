@@ -12,6 +12,48 @@ const TRANSACTION_RS: &str = include_str!("transaction.rs");
 const RESTRICTION_BACS: &str = include_str!("restriction_bacs.yaml");
 const RESTRICTION_BACS_RS: &str = include_str!("restriction_bacs.rs");
 
+const PET_VARIANT: &str = include_str!("pet_variant.yaml");
+
+const PET_NESTED_VARIANT: &str = include_str!("pet_nested_variant.yaml");
+const PET_NESTED_VARIANT_RS: &str = include_str!("pet_nested_variant.rs");
+
+const PET_DISCRIMINATOR: &str = include_str!("pet_discriminator.yaml");
+const PET_DISCRIMINATOR_RS: &str = include_str!("pet_discriminator.rs");
+
+/// The expected code for `Cat` and `Pet` in `PET_DISCRIMINATOR`, compiled.
+mod pet_discriminator {
+    use serde::{Deserialize, Serialize};
+
+    include!("pet_discriminator.rs");
+}
+
+/// The expected code for `PET_NESTED_VARIANT`, compiled, with the models it refers to.
+mod pet_nested_variant {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Cat {
+        pub meows: bool,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Dog {
+        pub barks: bool,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Person {
+        pub name: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Shelter {
+        pub address: String,
+    }
+
+    include!("pet_nested_variant.rs");
+}
+
 
 fn record_for_schema(name: &str, schema: &str, spec: &OpenAPI) -> Record {
     let schema = serde_yaml::from_str::<Schema>(schema).unwrap();
@@ -49,4 +91,84 @@ fn test_nullable_doesnt_deref() {
     let record = record_for_schema("PaymentInitiationOptionalRestrictionBacs", RESTRICTION_BACS, &spec);
     let code = formatted_code(record, &HirSpec::default());
     assert_eq!(code, RESTRICTION_BACS_RS);
-}
\ No newline at end of file
+}
+#[test]
+fn test_nested_one_of() {
+    let mut spec = OpenAPI::default();
+    spec.schemas.insert("PetBase", Schema::new_object());
+    spec.schemas.insert("Cat", Schema::new_object());
+    spec.schemas.insert("Dog", Schema::new_object());
+
+    let schema = serde_yaml::from_str::<Schema>(PET_VARIANT).unwrap();
+    let mut result = HirSpec::default();
+    extract_records(&spec, &mut result).unwrap();
+    for record in ln_core::extractor::create_nested_records("Pet", &schema, &spec) {
+        result.schemas.insert(record.name().to_string(), record);
+    }
+    let record = ln_core::extractor::create_record("Pet", &schema, &spec);
+    result.schemas.insert("Pet".to_string(), record.clone());
+
+    let code = formatted_code(record, &result);
+    assert!(code.contains("#[serde(flatten)]\n    pub pet_base: PetBase,"));
+    assert!(code.contains("#[serde(flatten)]\n    pub variant: PetVariant,"));
+    assert!(code.contains("pub nickname: Option<String>,"));
+
+    let code = formatted_code(result.schemas["PetVariant"].clone(), &result);
+    assert!(code.contains("#[serde(untagged)]\npub enum PetVariant {\n    Cat(Cat),\n    Dog(Dog),\n}"));
+}
+
+#[test]
+fn test_one_of_in_nested_all_of() {
+    let mut spec = OpenAPI::default();
+    for name in ["Cat", "Dog", "Person", "Shelter"] {
+        spec.schemas.insert(name, Schema::new_object());
+    }
+
+    let schema = serde_yaml::from_str::<Schema>(PET_NESTED_VARIANT).unwrap();
+    let mut result = HirSpec::default();
+    extract_records(&spec, &mut result).unwrap();
+    let unions = ln_core::extractor::create_nested_records("Pet", &schema, &spec);
+    assert_eq!(unions.iter().map(|r| r.name()).collect::<Vec<_>>(), ["PetVariant", "PetVariant2"]);
+    for record in &unions {
+        result.schemas.insert(record.name().to_string(), record.clone());
+    }
+    let record = ln_core::extractor::create_record("Pet", &schema, &spec);
+
+    let mut code = formatted_code(record, &result);
+    for record in unions {
+        code += &formatted_code(record, &result);
+    }
+    // Both are formatted the same way first, since the expected code is formatted by hand.
+    let format = |code: &str| prettyplease::unparse(&syn::parse_file(code).unwrap());
+    assert_eq!(format(&code), format(PET_NESTED_VARIANT_RS));
+
+    use pet_nested_variant::*;
+    let pet = Pet::new(PetVariant::Cat(Cat { meows: true }), PetVariant2::Person(Person { name: "Ana".to_string() }));
+    let json = serde_json::to_value(&pet).unwrap();
+    assert_eq!(json, serde_json::json!({"meows": true, "name": "Ana"}));
+    let pet = Pet::try_from(json).unwrap();
+    assert!(matches!(pet.variant, PetVariant::Cat(Cat { meows: true })));
+    assert!(matches!(pet.variant2, PetVariant2::Person(Person { ref name }) if name == "Ana"));
+}
+
+#[test]
+fn test_discriminator_subtype_keeps_tag() {
+    let spec: OpenAPI = serde_yaml::from_str(PET_DISCRIMINATOR).unwrap();
+    let mut result = HirSpec::default();
+    extract_records(&spec, &mut result).unwrap();
+
+    let code = formatted_code(result.schemas["Cat"].clone(), &result) + &formatted_code(result.schemas["Pet"].clone(), &result);
+    let format = |code: &str| prettyplease::unparse(&syn::parse_file(code).unwrap());
+    assert_eq!(format(&code), format(PET_DISCRIMINATOR_RS));
+
+    use pet_discriminator::*;
+    // A subtype on its own still has the tag.
+    let cat = Cat::new("Tom".to_string(), "cat".to_string());
+    let json = serde_json::to_value(&cat).unwrap();
+    assert_eq!(json, serde_json::json!({"petType": "cat", "name": "Tom"}));
+    assert_eq!(Cat::try_from(json.clone()).unwrap().pet_type, "cat");
+    // The union writes the tag exactly once, and reads the subtype back from it.
+    let pet = Pet::try_from(json).unwrap();
+    assert!(matches!(&pet, Pet::Cat { name, lives_left: None } if name == "Tom"));
+    assert_eq!(serde_json::to_string(&pet).unwrap(), r#"{"petType":"cat","name":"Tom"}"#);
+}
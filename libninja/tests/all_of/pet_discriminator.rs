@@ -0,0 +1,47 @@
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Cat {
+    #[serde(rename = "livesLeft")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lives_left: Option<i64>,
+    pub name: String,
+    #[serde(rename = "petType")]
+    pub pet_type: String,
+}
+impl std::fmt::Display for Cat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+impl Cat {
+    pub fn new(name: String, pet_type: String) -> Self {
+        Self { lives_left: None, name, pet_type }
+    }
+}
+impl TryFrom<serde_json::Value> for Cat {
+    type Error = serde_json::Error;
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value)
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "petType")]
+#[serde(rename_all_fields = "camelCase")]
+pub enum Pet {
+    #[serde(rename = "cat")]
+    Cat {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lives_left: Option<i64>,
+        name: String,
+    },
+    Dog {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        barks: Option<bool>,
+        name: String,
+    },
+}
+impl TryFrom<serde_json::Value> for Pet {
+    type Error = serde_json::Error;
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value)
+    }
+}
\ No newline at end of file
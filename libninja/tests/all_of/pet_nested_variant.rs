@@ -0,0 +1,58 @@
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pet {
+    #[serde(flatten)]
+    pub variant: PetVariant,
+    #[serde(flatten)]
+    pub variant2: PetVariant2,
+}
+impl std::fmt::Display for Pet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+impl std::ops::Deref for Pet {
+    type Target = PetVariant;
+    fn deref(&self) -> &Self::Target {
+        &self.variant
+    }
+}
+impl std::ops::DerefMut for Pet {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.variant
+    }
+}
+impl Pet {
+    pub fn new(variant: PetVariant, variant2: PetVariant2) -> Self {
+        Self { variant, variant2 }
+    }
+}
+impl TryFrom<serde_json::Value> for Pet {
+    type Error = serde_json::Error;
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value)
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PetVariant {
+    Cat(Cat),
+    Dog(Dog),
+}
+impl TryFrom<serde_json::Value> for PetVariant {
+    type Error = serde_json::Error;
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value)
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PetVariant2 {
+    Person(Person),
+    Shelter(Shelter),
+}
+impl TryFrom<serde_json::Value> for PetVariant2 {
+    type Error = serde_json::Error;
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value)
+    }
+}
\ No newline at end of file
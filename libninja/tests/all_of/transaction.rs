@@ -50,4 +50,29 @@ impl std::ops::DerefMut for Transaction {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.transaction_base
     }
+}
+impl Transaction {
+    pub fn new(
+        transaction_base: TransactionBase,
+        payment_channel: String,
+        transaction_code: String,
+    ) -> Self {
+        Self {
+            transaction_base,
+            authorized_date: None,
+            authorized_datetime: None,
+            counterparties: None,
+            datetime: None,
+            payment_channel,
+            personal_finance_category: None,
+            personal_finance_category_icon_url: None,
+            transaction_code,
+        }
+    }
+}
+impl TryFrom<serde_json::Value> for Transaction {
+    type Error = serde_json::Error;
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value)
+    }
 }
\ No newline at end of file
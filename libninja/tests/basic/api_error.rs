@@ -0,0 +1,105 @@
+use std::fmt;
+use httpclient::InMemoryResponseExt;
+
+/// Error bodies longer than this are truncated in `Display` output.
+const MAX_BODY_SNIPPET: usize = 200;
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The server responded with an unsuccessful status code.
+    Http { operation: &'static str, status: u16, body: String },
+    /// The request failed before a response was received, or the response couldn't be read.
+    Protocol { operation: &'static str, source: httpclient::ProtocolError },
+}
+
+impl ClientError {
+    pub(crate) fn from_httpclient(operation: &'static str, error: httpclient::Error) -> Self {
+        match error {
+            httpclient::Error::HttpError(res) => {
+                let status = res.status().as_u16();
+                let body = res.text().unwrap_or_default();
+                ClientError::Http { operation, status, body }
+            }
+            httpclient::Error::Protocol(source) => ClientError::Protocol { operation, source },
+        }
+    }
+
+    /// The operation that failed.
+    pub fn operation(&self) -> &'static str {
+        match self {
+            ClientError::Http { operation, .. } => operation,
+            ClientError::Protocol { operation, .. } => operation,
+        }
+    }
+
+    /// The status code, if the server responded.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Http { status, .. } => Some(*status),
+            ClientError::Protocol { .. } => None,
+        }
+    }
+
+    /// Whether the request might succeed if sent again: connection errors and timeouts,
+    /// 429 Too Many Requests, and 5xx server errors.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Http { status, .. } => *status == 429 || (500..=599).contains(status),
+            ClientError::Protocol { source, .. } => matches!(
+                source,
+                httpclient::ProtocolError::ConnectionError(_) | httpclient::ProtocolError::IoError(_)
+            ),
+        }
+    }
+}
+
+fn snippet(body: &str) -> &str {
+    match body.char_indices().nth(MAX_BODY_SNIPPET) {
+        Some((i, _)) => &body[..i],
+        None => body,
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http { operation, status, body } => {
+                write!(f, "{} failed with status {}: {}", operation, status, snippet(body))
+            }
+            ClientError::Protocol { operation, source } => {
+                write!(f, "{} failed: {}", operation, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Http { .. } => None,
+            ClientError::Protocol { source, .. } => Some(source),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum ApiError {
+    NotFound(crate::model::NotFound),
+    Problem(crate::model::Problem),
+}
+
+impl ClientError {
+    /// The parsed error body, if the operation documents one for the response status.
+    /// Use `operation()` to tell which operation it came from.
+    pub fn api_error(&self) -> Option<ApiError> {
+        let ClientError::Http { operation, status, body } = self else {
+            return None;
+        };
+        match (*operation, *status) {
+            ("getPet", 404) => serde_json::from_str(body).ok().map(ApiError::NotFound),
+            ("getPet", 500..=599) => serde_json::from_str(body).ok().map(ApiError::Problem),
+            _ => None,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+impl PetstoreClient {
+    pub fn list_pets(&self, ids: impl AsRef<[i64]>) -> FluentRequest<'_, request::ListPetsRequest> {
+        FluentRequest {
+            client: self,
+            params: request::ListPetsRequest {
+                ids: ids.as_ref().to_vec(),
+            },
+        }
+    }
+}
\ No newline at end of file
@@ -2,11 +2,13 @@ use std::fs::File;
 use std::str::FromStr;
 
 use anyhow::Result;
-use hir::{HirSpec, Language};
+use hir::{HirSpec, Language, Location, Parameter, StringFormat, Ty};
 use libninja::{generate_library, rust};
+use libninja::rust::codegen::ToRustCode;
 use ln_core::extractor::{extract_api_operations, extract_inputs, extract_spec};
-use ln_core::{PackageConfig, OutputConfig};
+use ln_core::{ConfigFlags, PackageConfig, OutputConfig};
 use openapiv3::OpenAPI;
+use quote::ToTokens;
 use pretty_assertions::assert_eq;
 use std::path::PathBuf;
 
@@ -14,6 +16,103 @@ const BASIC: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/spec/basic.yaml"
 const RECURLY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/spec/recurly.yaml");
 
 const EXAMPLE: &str = include_str!("link_create_token.rs");
+const WITH_HTTP_CLIENT: &str = include_str!("with_http_client.rs");
+
+const LIST_PETS: &str = include_str!("list_pets.rs");
+
+/// `list_pets` as generated for `test_required_array_arg`, with just enough around it to compile.
+mod list_pets {
+    pub struct PetstoreClient;
+
+    pub struct FluentRequest<'a, T> {
+        pub client: &'a PetstoreClient,
+        pub params: T,
+    }
+
+    pub mod request {
+        pub struct ListPetsRequest {
+            pub ids: Vec<i64>,
+        }
+    }
+
+    include!("list_pets.rs");
+}
+
+const API_ERROR: &str = include_str!("api_error.rs");
+
+/// `error.rs` as generated for `test_api_error`, compiled.
+#[allow(dead_code)]
+mod api_error {
+    include!("api_error.rs");
+}
+
+/// The error models `api_error` refers to.
+mod model {
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+    pub struct NotFound {
+        pub id: String,
+    }
+
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+    pub struct Problem {
+        pub title: String,
+        pub status: u16,
+    }
+}
+
+/// A client with only the http client field, and `with_http_client` as generated.
+mod with_http_client {
+    use std::borrow::Cow;
+
+    pub struct PetstoreClient {
+        pub client: Cow<'static, httpclient::Client>,
+    }
+
+    include!("with_http_client.rs");
+}
+
+fn petstore_opt(config: ConfigFlags) -> PackageConfig {
+    PackageConfig {
+        package_name: "petstore".to_string(),
+        service_name: "Petstore".to_string(),
+        language: Language::Rust,
+        package_version: "0.1.0".to_string(),
+        config,
+        dest: PathBuf::from_str("..").unwrap(),
+    }
+}
+
+/// A required parameter, with everything else unset.
+fn param(name: &str, ty: Ty, location: Location) -> Parameter {
+    Parameter {
+        name: name.to_string(),
+        ty,
+        location,
+        optional: false,
+        doc: None,
+        example: None,
+        json_content: false,
+        default: None,
+        array_style: None,
+    }
+}
+
+/// The method `name` from the impl blocks in `code`, formatted the same way whatever the input's formatting.
+fn impl_fn(code: &str, name: &str) -> String {
+    let file = syn::parse_file(code).unwrap();
+    let method = file.items.into_iter()
+        .filter_map(|item| match item {
+            syn::Item::Impl(imp) => Some(imp.items),
+            _ => None,
+        })
+        .flatten()
+        .find_map(|item| match item {
+            syn::ImplItem::Fn(f) if f.sig.ident == name => Some(f),
+            _ => None,
+        })
+        .unwrap();
+    prettyplease::unparse(&syn::parse_quote!(impl Client { #method }))
+}
 
 #[test]
 pub fn test_required_args() {
@@ -34,10 +133,7 @@ fn test_generate_example() -> Result<()> {
     let opt = PackageConfig {
         package_name: "plaid".to_string(),
         service_name: "Plaid".to_string(),
-        language: Language::Rust,
-        package_version: "0.1.0".to_string(),
-        config: Default::default(),
-        dest: PathBuf::from_str("..").unwrap(),
+        ..petstore_opt(ConfigFlags::default())
     };
     let mut result = HirSpec::default();
     extract_api_operations(&spec, &mut result).unwrap();
@@ -70,3 +166,377 @@ pub fn test_build_full_library_recurly() -> Result<()> {
     };
     generate_library(spec, opts)
 }
+
+#[test]
+fn test_model_prefix() {
+    let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Pet'
+components:
+  schemas:
+    Pet:
+      type: object
+      required: [tag]
+      properties:
+        tag:
+          $ref: '#/components/schemas/Tag'
+    Tag:
+      type: object
+      properties:
+        name:
+          type: string
+"#).unwrap();
+    let mut spec = extract_spec(&spec).unwrap();
+    let config = ConfigFlags {
+        model_prefix: Some("Acme".to_string()),
+        ..ConfigFlags::default()
+    };
+    spec.rename_models(|name| config.model_name(name));
+    assert!(spec.schemas.contains_key("Acme Pet"));
+    assert!(spec.schemas.contains_key("Acme Tag"));
+    assert!(matches!(&spec.get_operation("listPets").unwrap().ret, Ty::Model(name) if name == "Acme Pet"));
+
+    let record = spec.get_record("Acme Pet").unwrap();
+    let code = rust::lower_mir::create_struct(record, &config, &spec);
+    let code = rust::format::format_code(code).unwrap();
+    assert!(code.contains("pub struct AcmePet {"));
+    assert!(code.contains("pub tag: AcmeTag,"));
+}
+
+#[test]
+fn test_strict_formats() {
+    let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Users
+  version: 1.0.0
+paths:
+  /users:
+    get:
+      operationId: listUsers
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/User'
+components:
+  schemas:
+    User:
+      type: object
+      required: [email, id, homepage]
+      properties:
+        email:
+          type: string
+          format: email
+        id:
+          type: string
+          format: uuid
+        homepage:
+          type: string
+          format: uri
+"#).unwrap();
+    let mut spec = extract_spec(&spec).unwrap();
+    let config = ConfigFlags {
+        strict_formats: [StringFormat::Email, StringFormat::Uuid].into_iter().collect(),
+        ..ConfigFlags::default()
+    };
+    spec.downgrade_formats(|format| !format.is_opt_in() || config.strict_formats.contains(&format));
+    let record = spec.get_record("User").unwrap();
+    let code = rust::lower_mir::create_struct(record, &config, &spec);
+    let code = rust::format::format_code(code).unwrap();
+    assert!(code.contains("pub email: crate::validated::Email,"));
+    assert!(code.contains("pub id: crate::validated::Uuid,"));
+    assert!(code.contains("pub homepage: String,"));
+
+    let formats = [StringFormat::Email, StringFormat::Uuid].into_iter().collect();
+    let code = rust::validated::generate_validated_rs(&formats, &Default::default(), &Default::default());
+    let code = rust::format::format_code(code).unwrap();
+    assert!(code.contains("pub struct Email(String);"));
+    assert!(code.contains("impl FromStr for Uuid {"));
+}
+
+#[test]
+fn test_not_schema() {
+    let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Settings
+  version: 1.0.0
+paths:
+  /settings:
+    get:
+      operationId: getSetting
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Setting'
+components:
+  schemas:
+    Setting:
+      not:
+        type: 'null'
+"#).unwrap();
+    let spec = extract_spec(&spec).unwrap();
+    let record = spec.get_record("Setting").unwrap();
+    let code = rust::lower_mir::create_struct(record, &ConfigFlags::default(), &spec);
+    let code = rust::format::format_code(code).unwrap();
+    assert!(code.contains("pub struct Setting(pub serde_json::Value);"));
+    assert!(code.contains("pub fn validate(&self) -> Result<(), String> {"));
+    assert!(code.contains("if self.0.is_null() {"));
+}
+
+#[test]
+fn test_response_envelope() {
+    let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      responses:
+        '200':
+          description: OK
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/PetList'
+components:
+  schemas:
+    PetList:
+      type: object
+      required: [data]
+      properties:
+        data:
+          type: array
+          items:
+            $ref: '#/components/schemas/Pet'
+    Pet:
+      type: object
+      properties:
+        name:
+          type: string
+"#).unwrap();
+    let mut spec = extract_spec(&spec).unwrap();
+    spec.unwrap_envelopes("data");
+    let operation = spec.get_operation("listPets").unwrap();
+    assert_eq!(operation.envelope.as_deref(), Some("data"));
+    let Ty::Array(inner) = &operation.ret else { panic!() };
+    assert!(matches!(inner.as_ref(), Ty::Model(name) if name == "Pet"));
+
+    let opt = petstore_opt(ConfigFlags::default());
+    let code = rust::request::impl_send_request(operation, &spec, &opt);
+    let code = rust::format::format_code(code).unwrap();
+    assert!(code.contains("type Output = Result<Vec<Pet>, crate::ClientError>;"));
+    assert!(code.contains("res.json::<Envelope<Vec<Pet>>>()"));
+    assert!(code.contains("#[serde(rename = \"data\")]"));
+}
+
+#[test]
+fn test_client_config_default() {
+    let spec = HirSpec {
+        servers: [("default".to_string(), "https://api.example.com".to_string())].into_iter().collect(),
+        ..HirSpec::default()
+    };
+    let opt = petstore_opt(ConfigFlags::default());
+    let code = rust::format::format_code(rust::client::client_config(&spec, &opt)).unwrap();
+    assert!(code.contains("impl Default for ClientConfig {"));
+    assert!(code.contains("timeout: std::time::Duration::from_secs(30),"));
+    assert!(code.contains("max_retries: 3,"));
+    assert!(code.contains("base_url: \"https://api.example.com\".to_string(),"));
+    assert!(code.contains(".timeout(self.timeout)"));
+    assert!(code.contains("httpclient::middleware::Retry::new()"));
+    assert!(code.contains(".max_retries(self.max_retries as usize)"));
+}
+
+#[test]
+fn test_with_http_client() {
+    let opt = petstore_opt(ConfigFlags::default());
+    let client = rust::client::struct_Client(&HirSpec::default(), &opt).to_rust_code();
+    assert_eq!(impl_fn(&client.to_string(), "with_http_client"), impl_fn(WITH_HTTP_CLIENT, "with_http_client"));
+
+    // Send a request from the method as generated, and check it goes to the server the custom client points at.
+    let server = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let custom = httpclient::Client::new().base_url(&format!("http://{}", server.local_addr().unwrap()));
+    let received = std::thread::spawn(move || {
+        use std::io::{BufRead, BufReader, Write};
+        let (mut stream, _) = server.accept().unwrap();
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line).unwrap();
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}").unwrap();
+        request_line
+    });
+    let client = with_http_client::PetstoreClient {
+        client: std::borrow::Cow::Owned(httpclient::Client::new().base_url("http://default.invalid")),
+    }.with_http_client(custom);
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async { client.client.get("/pets").await }).unwrap();
+    assert_eq!(received.join().unwrap().trim_end(), "GET /pets HTTP/1.1");
+}
+
+#[test]
+fn test_query_api_key() {
+    let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Weather
+  version: 1.0.0
+paths:
+  /forecast:
+    get:
+      operationId: getForecast
+      responses:
+        '204':
+          description: No content
+components:
+  securitySchemes:
+    ApiKeyAuth:
+      type: apiKey
+      in: query
+      name: api_key
+security:
+  - ApiKeyAuth: []
+"#).unwrap();
+    let spec = extract_spec(&spec).unwrap();
+    let opt = petstore_opt(ConfigFlags::default());
+    let authenticate = rust::client::build_Client_authenticate(&spec, &opt);
+    let code = rust::format::format_code(quote::quote!(impl PetstoreClient { #authenticate })).unwrap();
+    assert!(code.contains("r = r.query(\"api_key\", api_key);"));
+    assert!(!code.contains("r.header("));
+}
+
+#[test]
+fn test_tag_traits() {
+    let spec: OpenAPI = serde_yaml::from_str(r#"
+openapi: 3.0.0
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /users:
+    get:
+      operationId: listUsers
+      tags: [users]
+      responses:
+        '204':
+          description: No content
+  /health:
+    get:
+      operationId: health
+      responses:
+        '204':
+          description: No content
+"#).unwrap();
+    let spec = extract_spec(&spec).unwrap();
+    let opt = petstore_opt(ConfigFlags {
+        tag_traits: true,
+        ..ConfigFlags::default()
+    });
+    let traits = rust::client::tag_traits(&spec, &opt);
+    let code = rust::format::format_code(traits.clone()).unwrap();
+    assert!(code.contains("pub trait UsersApi {"));
+    assert!(code.contains("impl UsersApi for PetstoreClient {"));
+
+    // A mock outside the generated crate can implement the trait: it only names the request
+    // struct and the error, not the client's `FluentRequest`.
+    let file: syn::File = syn::parse2(traits).unwrap();
+    let users_api = file.items.iter().find_map(|item| match item {
+        syn::Item::Trait(t) if t.ident == "UsersApi" => Some(t),
+        _ => None,
+    }).unwrap();
+    let mock: syn::ItemImpl = syn::parse_quote! {
+        impl UsersApi for MockUsers {
+            fn list_users(&self, params: request::ListUsersRequest) -> ::futures::future::BoxFuture<'_, Result<(), crate::ClientError>> {
+                Box::pin(async { Ok(()) })
+            }
+        }
+    };
+    let declared: Vec<String> = users_api.items.iter().filter_map(|item| match item {
+        syn::TraitItem::Fn(f) => Some(f.sig.to_token_stream().to_string()),
+        _ => None,
+    }).collect();
+    let implemented: Vec<String> = mock.items.iter().filter_map(|item| match item {
+        syn::ImplItem::Fn(f) => Some(f.sig.to_token_stream().to_string()),
+        _ => None,
+    }).collect();
+    assert_eq!(declared, implemented);
+
+    // The client keeps its fluent methods for every operation.
+    let code = rust::format::format_code(rust::client::impl_Client(&spec, &opt)).unwrap();
+    assert!(code.contains("pub fn health(&self)"));
+    assert!(code.contains("pub fn list_users(&self)"));
+}
+
+#[test]
+fn test_api_error() {
+    let error = |status, model: &str| hir::ErrorResponse { status, ty: Ty::model(model) };
+    let spec = HirSpec {
+        operations: vec![hir::Operation {
+            name: "getPet".to_string(),
+            errors: vec![error(hir::ErrorStatus::Code(404), "NotFound"), error(hir::ErrorStatus::Range(5), "Problem")],
+            ..hir::Operation::default()
+        }],
+        ..HirSpec::default()
+    };
+    let config = ConfigFlags {
+        shared_errors: true,
+        ..ConfigFlags::default()
+    };
+    let code = rust::format::format_code(rust::error::generate_error_rs(&spec, &config)).unwrap();
+    let format = |code: &str| prettyplease::unparse(&syn::parse_file(code).unwrap());
+    assert_eq!(format(&code), format(API_ERROR));
+
+    use api_error::{ApiError, ClientError};
+    let http = |status, body: &str| ClientError::Http { operation: "getPet", status, body: body.to_string() };
+    let problem = http(500, r#"{"title": "Database unavailable", "status": 500}"#).api_error();
+    assert!(matches!(problem, Some(ApiError::Problem(model::Problem { ref title, status: 500 })) if title == "Database unavailable"));
+    // The body is only parsed with the schema documented for its status.
+    assert!(matches!(http(404, r#"{"id": "7"}"#).api_error(), Some(ApiError::NotFound(_))));
+    assert!(http(400, r#"{"title": "Bad request", "status": 400}"#).api_error().is_none());
+    assert!(http(500, "Internal Server Error").api_error().is_none());
+}
+
+#[test]
+fn test_required_array_arg() {
+    let operation = hir::Operation {
+        name: "listPets".to_string(),
+        method: "get".to_string(),
+        path: "/pets".to_string(),
+        parameters: vec![param("ids", Ty::Array(Box::new(Ty::integer())), Location::Query)],
+        ..hir::Operation::default()
+    };
+    let method = rust::client::build_api_client_method(&operation, &ConfigFlags::default());
+    let method = quote::quote!(impl PetstoreClient { #method }).to_string();
+    assert_eq!(impl_fn(&method, "list_pets"), impl_fn(LIST_PETS, "list_pets"));
+
+    use list_pets::PetstoreClient;
+    let client = PetstoreClient;
+    // A slice literal, so callers don't need to allocate.
+    let request = client.list_pets(&[1, 2]);
+    assert!(std::ptr::eq(request.client, &client));
+    assert_eq!(request.params.ids, vec![1, 2]);
+    // A `Vec` still works, by value or by reference.
+    let ids = vec![3];
+    assert_eq!(client.list_pets(&ids).params.ids, vec![3]);
+    assert_eq!(client.list_pets(ids).params.ids, vec![3]);
+}
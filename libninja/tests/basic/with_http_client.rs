@@ -0,0 +1,6 @@
+impl PetstoreClient {
+    pub fn with_http_client(mut self, client: httpclient::Client) -> Self {
+        self.client = Cow::Owned(client);
+        self
+    }
+}
\ No newline at end of file
@@ -0,0 +1,30 @@
+use openapiv3::OpenAPI;
+use pretty_assertions::assert_eq;
+
+use hir::Ty;
+use ln_core::extract_spec;
+
+const WEBHOOKS: &str = include_str!("webhooks.yaml");
+
+#[test]
+fn test_extract_webhooks() {
+    let spec: OpenAPI = serde_yaml::from_str(WEBHOOKS).unwrap();
+    let spec = extract_spec(&spec).unwrap();
+    assert_eq!(spec.webhooks.len(), 2);
+    assert!(matches!(&spec.webhooks[0].ty, Ty::Model(name) if name == "Pet"));
+    assert!(matches!(&spec.webhooks[1].ty, Ty::Model(name) if name == "PetAdoptedWebhook"));
+    assert!(spec.schemas.contains_key("Pet"));
+    assert!(spec.schemas.contains_key("PetAdoptedWebhook"));
+}
+
+#[test]
+fn test_generate_webhook_event() {
+    let spec: OpenAPI = serde_yaml::from_str(WEBHOOKS).unwrap();
+    let spec = extract_spec(&spec).unwrap();
+    let code = libninja::rust::webhook::generate_webhook_rs(&spec);
+    let code = libninja::rust::format::format_code(code).unwrap();
+    assert!(code.contains("pub enum WebhookEvent {"));
+    assert!(code.contains("NewPet(Pet)"));
+    assert!(code.contains("PetAdopted(PetAdoptedWebhook)"));
+    assert!(code.contains("\"petAdopted\" => serde_json::from_str(payload).map(Self::PetAdopted)"));
+}
@@ -82,6 +82,8 @@ pub struct Class<T> {
 
     pub mut_self_instance_methods: Vec<Function<T>>,
     pub lifetimes: Vec<String>,
+    /// Type parameters, e.g. `T` for a `Page<T>` envelope.
+    pub generics: Vec<String>,
     pub decorators: Vec<T>,
     pub superclasses: Vec<T>,
 }
@@ -108,6 +110,7 @@ pub struct Import {
     /// If a wildcard import and if we want to alias, then alias
     pub alias: Option<String>,
     pub vis: Visibility,
+    /// Cargo feature the import is gated on, for re-exports of items behind that feature.
     pub feature: Option<String>
 }
 
@@ -160,6 +163,7 @@ impl<T> Default for Class<T> {
             public: false,
             mut_self_instance_methods: vec![],
             lifetimes: vec![],
+            generics: vec![],
             decorators: vec![],
             superclasses: vec![],
         }
@@ -180,6 +184,7 @@ impl Debug for Class<String> {
         public: {public}, \
         mut_self_instance_methods: todo!, \
         lifetimes: todo!, \
+        generics: todo!, \
         superclasses: todo! }}",
                name = self.name,
                doc = self.doc,
@@ -256,6 +261,11 @@ impl Import {
         self.vis = Visibility::Public;
         self
     }
+
+    pub fn feature(mut self, feature: &str) -> Self {
+        self.feature = Some(feature.to_string());
+        self
+    }
 }
 
 impl<T> Default for File<T>